@@ -0,0 +1,192 @@
+//! Throughput and key setup benchmarks of the block ciphers.
+//!
+//! Run with `cargo bench --bench ciphers`. Results are saved to
+//! `target/bench/ciphers-latest.txt`; `-- --save-baseline` stores them
+//! as `target/bench/ciphers-baseline.txt` too. When a baseline exists,
+//! every result is compared with it (e.g. before and after changing `f`).
+//!
+//! `BENCH_TIME_MS` sets measuring time of one case (default 300 ms),
+//! `BENCH_FILTER` runs only cases whose name contains its value.
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+use rs_samples::crypto::blowfish::Blowfish;
+use rs_samples::crypto::gost::Gost;
+use rs_samples::crypto::kuznyechik::Kuznyechik;
+use rs_samples::crypto::magma::Magma;
+use rs_samples::crypto::twofish::Twofish;
+use rs_samples::crypto::modes::{self, Parallel};
+use rs_samples::crypto::{BlockCipher, Padding};
+
+const RESULTS_DIR: &str = "target/bench";
+const LATEST: &str = "ciphers-latest.txt";
+const BASELINE: &str = "ciphers-baseline.txt";
+// change smaller than this is reported as noise (percent)
+const NOISE: f64 = 5.0;
+const SIZES: [usize; 4] = [64, 1024, 64 * 1024, 1024 * 1024];
+
+const KEY: &[u8; 32] = b"12345678901234567890123456789012";
+// first block size bytes are used as IV vector and nonce
+const IV: &[u8; 16] = b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10";
+
+/// Result of one case: how long one call takes and how many bytes it processes.
+struct Measurement {
+    name: String,
+    nanos: f64,
+    nbytes: usize,
+}
+
+impl Measurement {
+    /// Value saved in report: MB/s for throughput, ns/op for latency.
+    fn value(&self) -> f64 {
+        if self.nbytes > 0 {
+            self.nbytes as f64 / self.nanos * 1e9 / 1e6
+        } else {
+            self.nanos
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        if self.nbytes > 0 { "MB/s" } else { "ns/op" }
+    }
+
+    /// Checks if bigger value is better.
+    fn higher_is_better(&self) -> bool {
+        self.nbytes > 0
+    }
+}
+
+struct Bench {
+    time: Duration,
+    filter: Option<String>,
+    results: Vec<Measurement>,
+}
+
+impl Bench {
+    /// Measures `f` called repeatedly, `nbytes` is the size of processed data (0 for latency).
+    fn run<T, F: FnMut() -> T>(&mut self, name: &str, nbytes: usize, mut f: F) {
+        if let Some(filter) = &self.filter {
+            if !name.contains(filter.as_str()) {
+                return;
+            }
+        }
+
+        // warm up and estimate number of iterations per sample
+        let start = Instant::now();
+        let mut iterations = 0u64;
+        while start.elapsed() < self.time / 10 {
+            black_box(f());
+            iterations += 1;
+        }
+        let batch = iterations.max(1);
+
+        // the best of samples is the least disturbed by other processes
+        let mut best = f64::MAX;
+        let start = Instant::now();
+        while start.elapsed() < self.time {
+            let t = Instant::now();
+            for _ in 0..batch {
+                black_box(f());
+            }
+            best = best.min(t.elapsed().as_nanos() as f64 / batch as f64);
+        }
+
+        let m = Measurement { name: name.to_string(), nanos: best, nbytes };
+        println!("{:<36} {:>12.2} {}", m.name, m.value(), m.unit());
+        self.results.push(m);
+    }
+}
+
+fn bench_cipher<C: BlockCipher>(bench: &mut Bench, name: &str, cipher: &C) {
+    let mut block = C::Block::default();
+    bench.run(&format!("{}/block", name), 0, || {
+        cipher.encrypt_block(black_box(&mut block));
+        block
+    });
+
+    let iv = &IV[..C::BLOCK_SIZE];
+    let parallel = Parallel::new().with_threshold(0);
+    for nbytes in SIZES {
+        let plain = vec![0x5au8; nbytes];
+        let cbc = modes::encrypt_cbc_iv_with(cipher, &plain, iv, Padding::None).unwrap();
+        let mut data = plain.clone();
+
+        bench.run(&format!("{}/ecb/{}", name, nbytes), nbytes, || modes::encrypt_ecb_with(cipher, &plain, Padding::None));
+        bench.run(&format!("{}/cbc-encrypt/{}", name, nbytes), nbytes, || modes::encrypt_cbc_iv_with(cipher, &plain, iv, Padding::None));
+        bench.run(&format!("{}/cbc-decrypt/{}", name, nbytes), nbytes, || modes::decrypt_cbc_with(cipher, &cbc, Padding::None));
+        bench.run(&format!("{}/ctr/{}", name, nbytes), nbytes, || modes::apply_ctr(cipher, iv, 0, &mut data));
+        bench.run(&format!("{}/cfb/{}", name, nbytes), nbytes, || modes::encrypt_cfb_iv(cipher, &plain, iv));
+        bench.run(&format!("{}/ofb/{}", name, nbytes), nbytes, || modes::encrypt_ofb_iv(cipher, &plain, iv));
+        bench.run(&format!("{}/ctr-parallel/{}", name, nbytes), nbytes, || parallel.apply_ctr(cipher, iv, 0, &mut data));
+    }
+}
+
+/// Reads report saved by previous run: name -> value.
+fn read_report(path: &Path) -> BTreeMap<String, f64> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some((fields.next()?.to_string(), fields.next()?.parse().ok()?))
+        })
+        .collect()
+}
+
+fn write_report(path: &Path, results: &[Measurement]) {
+    let text: String = results.iter().map(|m| format!("{}\t{:.3}\t{}\n", m.name, m.value(), m.unit())).collect();
+    fs::create_dir_all(RESULTS_DIR).and_then(|_| fs::write(path, text)).expect("cannot save report");
+}
+
+/// Prints results which differ from the baseline by more than noise.
+fn compare(baseline: &BTreeMap<String, f64>, results: &[Measurement]) {
+    println!("\ncompared with baseline (changes above {}%):", NOISE);
+    let mut regressions = 0;
+    for m in results {
+        let Some(&old) = baseline.get(&m.name) else { continue };
+        let change = (m.value() - old) / old * 100.0;
+        if change.abs() < NOISE {
+            continue;
+        }
+        let better = (change > 0.0) == m.higher_is_better();
+        if !better {
+            regressions += 1;
+        }
+        println!("{:<36} {:>12.2} -> {:>12.2} {:<6} {:+7.1}% {}",
+                 m.name, old, m.value(), m.unit(), change, if better { "improved" } else { "REGRESSED" });
+    }
+    println!("{} regression(s)", regressions);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let millis = env::var("BENCH_TIME_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(300);
+    let mut bench = Bench { time: Duration::from_millis(millis), filter: env::var("BENCH_FILTER").ok(), results: Vec::new() };
+
+    // Blowfish key setup is 521 block encryptions, GOST only converts the key
+    bench.run("blowfish/key-setup", 0, || Blowfish::new(black_box(KEY)));
+    bench.run("gost/key-setup", 0, || Gost::new(black_box(KEY)));
+    bench.run("magma/key-setup", 0, || Magma::new(black_box(KEY)));
+    bench.run("twofish/key-setup", 0, || Twofish::new(black_box(KEY)));
+    bench.run("kuznyechik/key-setup", 0, || Kuznyechik::new(black_box(KEY)));
+
+    bench_cipher(&mut bench, "blowfish", &Blowfish::new(KEY).unwrap());
+    bench_cipher(&mut bench, "gost", &Gost::new(KEY).unwrap());
+    bench_cipher(&mut bench, "magma", &Magma::new(KEY).unwrap());
+    bench_cipher(&mut bench, "twofish", &Twofish::new(KEY).unwrap());
+    bench_cipher(&mut bench, "kuznyechik", &Kuznyechik::new(KEY).unwrap());
+
+    let dir = Path::new(RESULTS_DIR);
+    let baseline = read_report(&dir.join(BASELINE));
+    if !baseline.is_empty() {
+        compare(&baseline, &bench.results);
+    }
+    write_report(&dir.join(LATEST), &bench.results);
+    if args.iter().any(|a| a == "--save-baseline") {
+        write_report(&dir.join(BASELINE), &bench.results);
+        println!("\nbaseline saved");
+    }
+}
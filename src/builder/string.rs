@@ -1,5 +1,4 @@
 use std::io::Write;
-use std::iter;
 
 const DEFAULT_CAPACITY: usize = 128;
 const SPACE: char = ' ';
@@ -15,6 +14,7 @@ impl StringBuilder {
     }
 
     /// Adds to builder's buffer item satisfying BytesConvertible trait
+    #[allow(clippy::should_implement_trait)]
     pub fn add<T: BytesCovertible>(mut self, data: T) -> Self {
         self.0.write_all(&data.to_bytes()).unwrap();
         self
@@ -38,7 +38,7 @@ impl StringBuilder {
 
     /// Checks if buffer is empty
     pub fn empty(&self) -> bool {
-        self.0.len() == 0
+        self.0.is_empty()
     }
 
     /// Returns string representation of bytes
@@ -60,7 +60,7 @@ impl StringBuilder {
 
 /// Creates bytes vector from bytes slice
 fn as_vector(s: &[u8]) -> Vec<u8> {
-    let mut buffer = iter::repeat(0).take(s.len()).collect::<Vec<u8>>();
+    let mut buffer = std::iter::repeat_n(0, s.len()).collect::<Vec<u8>>();
     buffer.copy_from_slice(s);
     buffer
 }
@@ -83,7 +83,7 @@ impl BytesCovertible for String {
 }
 
 /// Converts '&str' to bytes vector
-impl<'a> BytesCovertible for &'a str {
+impl BytesCovertible for &str {
     fn to_bytes(&self) -> Vec<u8> {
         as_vector(self.as_bytes())
     }
@@ -105,7 +105,7 @@ impl BytesCovertible for char {
 }
 
 /// Converts bytes slice to bytes vector
-impl<'a> BytesCovertible for &'a [u8] {
+impl BytesCovertible for &[u8] {
     fn to_bytes(&self) -> Vec<u8> {
         as_vector(self)
     }
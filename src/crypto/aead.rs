@@ -0,0 +1,164 @@
+//! Authenticated encryption with associated data (encrypt-then-MAC).
+//!
+//! Plain text is encrypted in CTR mode with counter blocks made of the nonce
+//! (block size - 4 bytes) and 32 bit big-endian block counter starting at 0,
+//! so different nonces never share key stream. Then the cipher text and the
+//! associated data are authenticated with GOST 28147-89 imitovstavka.
+//! Encryption and MAC keys are derived from the passed key with HMAC-SHA256,
+//! so one key can be used with every supported cipher.
+//!
+//! MAC input (integers are big-endian):
+//! ```text
+//! aad length  8 bytes
+//! text length 8 bytes
+//! nonce       block size - 4 bytes
+//! aad         bytes
+//! cipher text bytes
+//! ```
+//! Sealed data is the cipher text followed by 8 byte tag.
+//! Nonce must never be reused with the same key.
+
+use crate::crypto::*;
+use crate::crypto::gost::{Gost, GostMac};
+use crate::crypto::sha256::HmacSha256;
+
+/// Size of the authentication tag.
+pub const TAG_SIZE: usize = 8;
+/// Size of the block counter, the nonce takes the rest of the block.
+pub const COUNTER_SIZE: usize = 4;
+
+/// Encrypts and authenticates plain text, authenticates associated data.
+/// Returns cipher text followed by the tag.
+pub fn seal<C: BlockCipher>(key: &[u8], nonce: &[u8], aad: &[u8], plain: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    check_nonce::<C>(nonce)?;
+    let (cipher, mac) = keys::<C>(key)?;
+    let mut data = plain.to_vec();
+    ctr(&cipher, nonce, &mut data)?;
+    let tag = tag(&mac, nonce, aad, &data);
+    data.extend(tag);
+    Ok(data)
+}
+
+/// Checks the tag and decrypts sealed data.
+/// Nothing is decrypted if the tag does not match.
+pub fn open<C: BlockCipher>(key: &[u8], nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if sealed.len() < TAG_SIZE {
+        return Err(CryptoError::InvalidFormat("sealed data is shorter than tag"));
+    }
+    check_nonce::<C>(nonce)?;
+
+    let (cipher, mac) = keys::<C>(key)?;
+    let (encrypted, expected) = sealed.split_at(sealed.len() - TAG_SIZE);
+    if !constant_time_eq(&tag(&mac, nonce, aad, encrypted), expected) {
+        return Err(CryptoError::AuthenticationFailed);
+    }
+
+    let mut data = encrypted.to_vec();
+    ctr(&cipher, nonce, &mut data)?;
+    Ok(data)
+}
+
+fn check_nonce<C: BlockCipher>(nonce: &[u8]) -> Result<(), CryptoError> {
+    if nonce.len() != C::BLOCK_SIZE - COUNTER_SIZE {
+        return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE - COUNTER_SIZE, actual: nonce.len() });
+    }
+    Ok(())
+}
+
+/// XORs data with key stream of counter blocks nonce || counter.
+/// Data longer than 2^32 blocks would wrap the counter and is rejected.
+fn ctr<C: BlockCipher>(cipher: &C, nonce: &[u8], data: &mut [u8]) -> Result<(), CryptoError> {
+    if data.len().div_ceil(C::BLOCK_SIZE) as u64 > 1 << (8 * COUNTER_SIZE) {
+        return Err(CryptoError::InvalidParameter("message is too long for the block counter"));
+    }
+    let mut block = C::Block::default();
+    block.as_mut()[..nonce.len()].copy_from_slice(nonce);
+    for (i, chunk) in data.chunks_mut(C::BLOCK_SIZE).enumerate() {
+        let mut gamma = block;
+        gamma.as_mut()[nonce.len()..].copy_from_slice(&(i as u32).to_be_bytes());
+        cipher.encrypt_block(&mut gamma);
+        xor_into(chunk, gamma.as_ref());
+    }
+    Ok(())
+}
+
+/// Derives encryption and MAC keys from the master key.
+fn keys<C: BlockCipher>(key: &[u8]) -> Result<(C, Gost), CryptoError> {
+    if key.is_empty() {
+        return Err(CryptoError::InvalidParameter("key is empty"));
+    }
+    let mut enc_key = HmacSha256::mac(key, b"aead encryption key");
+    let mut mac_key = HmacSha256::mac(key, b"aead mac key");
+    let keys = C::new(&enc_key).and_then(|cipher| Ok((cipher, Gost::new(&mac_key)?)));
+    zeroize(&mut enc_key);
+    zeroize(&mut mac_key);
+    keys
+}
+
+fn tag(mac: &Gost, nonce: &[u8], aad: &[u8], encrypted: &[u8]) -> Vec<u8> {
+    let mut imit = GostMac::with_tag_size(mac, TAG_SIZE).expect("valid tag size");
+    imit.update(&(aad.len() as u64).to_be_bytes());
+    imit.update(&(encrypted.len() as u64).to_be_bytes());
+    imit.update(nonce);
+    imit.update(aad);
+    imit.update(encrypted);
+    imit.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::blowfish::Blowfish;
+
+    const KEY: &[u8] = b"Trzy po trzy";
+    const NONCE: &[u8; 4] = b"1234";
+
+    fn round_trip<C: BlockCipher>() {
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+        let aad = b"header";
+        let sealed = seal::<C>(KEY, NONCE, aad, plain).unwrap();
+        assert_eq!(sealed.len(), plain.len() + TAG_SIZE);
+        assert_ne!(&sealed[..plain.len()], plain);
+        assert_eq!(open::<C>(KEY, NONCE, aad, &sealed).unwrap(), plain);
+
+        let sealed = seal::<C>(KEY, NONCE, &[], &[]).unwrap();
+        assert_eq!(open::<C>(KEY, NONCE, &[], &sealed).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip::<Blowfish>();
+        round_trip::<Gost>();
+    }
+
+    #[test]
+    fn test_tampered() {
+        let plain = b"Yamato & Musashi";
+        let aad = b"header";
+        let sealed = seal::<Gost>(KEY, NONCE, aad, plain).unwrap();
+
+        for i in 0..sealed.len() {
+            let mut corrupted = sealed.clone();
+            corrupted[i] ^= 0x80;
+            assert_eq!(open::<Gost>(KEY, NONCE, aad, &corrupted), Err(CryptoError::AuthenticationFailed));
+        }
+        assert!(open::<Gost>(KEY, NONCE, b"Header", &sealed).is_err());
+        assert!(open::<Gost>(KEY, b"1235", aad, &sealed).is_err());
+        assert!(open::<Gost>(KEY, b"12345678", aad, &sealed).is_err());
+        assert!(open::<Gost>(b"Trzy po cztery", NONCE, aad, &sealed).is_err());
+        assert!(open::<Gost>(KEY, NONCE, aad, &sealed[..TAG_SIZE - 1]).is_err());
+    }
+
+    /// Key streams of consecutive nonces (message counters) must not overlap.
+    #[test]
+    fn test_sequential_nonces() {
+        let zeros = vec![0u8; 64 * 8];
+        let mut blocks = std::collections::HashSet::new();
+        for n in 0u32..4 {
+            let sealed = seal::<Gost>(KEY, &n.to_be_bytes(), &[], &zeros).unwrap();
+            for block in sealed[..zeros.len()].chunks(8) {
+                assert!(blocks.insert(block.to_vec()), "key stream is reused for nonce {}", n);
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! bcrypt password hashing (Provos & Mazières) built on the Blowfish key schedule.
+//! Produces `$2b$` hash strings compatible with OpenBSD.
+
+use crate::crypto::*;
+use crate::crypto::blowfish::Blowfish;
+
+pub const DEFAULT_COST: u32 = 12;
+const MIN_COST: u32 = 4;
+const MAX_COST: u32 = 31;
+const SALT_SIZE: usize = 16;
+// password is truncated to 72 bytes (including terminating zero)
+const MAX_PASSWORD_SIZE: usize = 72;
+// "OrpheanBeholderScryDoubt" as big-endian words
+const CTEXT: [u32; 6] = [0x4f727068, 0x65616e42, 0x65686f6c, 0x64657253, 0x63727944, 0x6f756274];
+const ALPHABET: &[u8; 64] = b"./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Hashes password with random salt.
+pub fn hash<T: AsRef<[u8]>>(password: T, cost: u32) -> Result<String, CryptoError> {
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&random_bytes(SALT_SIZE));
+    hash_with_salt(password, cost, &salt)
+}
+
+/// Hashes password with passed salt.
+pub fn hash_with_salt<T: AsRef<[u8]>>(password: T, cost: u32, salt: &[u8; SALT_SIZE]) -> Result<String, CryptoError> {
+    if !(MIN_COST..=MAX_COST).contains(&cost) {
+        return Err(CryptoError::InvalidParameter("bcrypt cost must be 4..=31"));
+    }
+    let digest = raw_hash(password.as_ref(), cost, salt);
+    Ok(format!("$2b${:02}${}{}", cost, encode(salt), encode(&digest)))
+}
+
+/// Checks if password matches passed hash string ($2a$, $2b$ or $2y$).
+pub fn verify<T: AsRef<[u8]>>(password: T, hash: &str) -> Result<bool, CryptoError> {
+    let (cost, salt) = parse(hash)?;
+    let expected = hash_with_salt(password, cost, &salt)?;
+    // prefix ($2a$, $2y$) is not a part of comparison
+    Ok(constant_time_eq(&expected.as_bytes()[4..], &hash.as_bytes()[4..]))
+}
+
+/// Reads cost and salt from hash string.
+fn parse(hash: &str) -> Result<(u32, [u8; SALT_SIZE]), CryptoError> {
+    let bytes = hash.as_bytes();
+    if bytes.len() != 60 || !hash.is_ascii() {
+        return Err(CryptoError::InvalidFormat("bcrypt hash string"));
+    }
+    match &hash[..4] {
+        "$2a$" | "$2b$" | "$2y$" => (),
+        _ => return Err(CryptoError::InvalidFormat("unsupported bcrypt version")),
+    }
+    if bytes[6] != b'$' {
+        return Err(CryptoError::InvalidFormat("bcrypt hash string"));
+    }
+    let cost = hash[4..6].parse::<u32>().map_err(|_| CryptoError::InvalidFormat("bcrypt cost"))?;
+
+    let decoded = decode(&hash[7..29])?;
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&decoded[..SALT_SIZE]);
+    Ok((cost, salt))
+}
+
+/// EksBlowfish setup followed by 64 encryptions of CTEXT.
+/// Returns 23 bytes of digest.
+fn raw_hash(password: &[u8], cost: u32, salt: &[u8; SALT_SIZE]) -> Vec<u8> {
+    let mut key = password.to_vec();
+    key.push(0);
+    key.truncate(MAX_PASSWORD_SIZE);
+
+    let mut bf = Blowfish::initial();
+    bf.expand_key(&key, salt);
+    for _ in 0..(1u64 << cost) {
+        bf.expand_key(&key, &[]);
+        bf.expand_key(salt, &[]);
+    }
+    zeroize(&mut key);
+
+    let mut ctext = CTEXT;
+    for pair in ctext.chunks_mut(2) {
+        for _ in 0..64 {
+            let x = bf.encrypt(pair[0], pair[1]);
+            pair[0] = x.0;
+            pair[1] = x.1;
+        }
+    }
+
+    let mut digest: Vec<u8> = ctext.iter().flat_map(|w| w.to_be_bytes()).collect();
+    digest.truncate(23);
+    digest
+}
+
+/// Encodes bytes with bcrypt's base64 alphabet (no padding).
+fn encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity((data.len() * 4).div_ceil(3));
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        for i in 0..=chunk.len() {
+            text.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+        }
+    }
+    text
+}
+
+/// Decodes text in bcrypt's base64 alphabet.
+fn decode(text: &str) -> Result<Vec<u8>, CryptoError> {
+    let mut data = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() < 2 {
+            return Err(CryptoError::InvalidFormat("bcrypt base64 text"));
+        }
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let v = ALPHABET.iter().position(|a| a == c).ok_or(CryptoError::InvalidFormat("bcrypt base64 character"))?;
+            n |= (v as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            data.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cost 2^5 and 2^6 key setups are slow with `constant-time` feature
+    #[test]
+    #[cfg_attr(feature = "constant-time", ignore)]
+    fn test_vectors() {
+        // OpenBSD/jBCrypt test vectors
+        let vectors = [
+            ("U*U", "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"),
+            ("", "$2a$06$DCq7YPn5Rq63x1Lad4cll.TV4S6ytwfsfvkgY8jIucDrjc8deX1s."),
+            ("a", "$2a$06$m0CrhHm10qJ3lXRY.5zDGO3rS2KdeeWLuGmsfGlMfOxih58VYVfxe"),
+            ("abc", "$2a$06$If6bvum7DFjUnE9p2uDeDu0YHzrHM6tf.iqN8.yx.jNN1ILEf7h0i"),
+            ("abcdefghijklmnopqrstuvwxyz", "$2a$06$.rCVZVOThsIa97pEDOxvGuRRgzG64bvtJ0938xuqzv18d3ZpQhstC"),
+            ("~!@#$%^&*()      ~!@#$%^&*()PNBFRD", "$2a$06$fPIsBO8qRqkjj273rfaOI.HtSV9jLDpTbZn782DC6/t7qT67P6FfO"),
+        ];
+        for (password, expected) in vectors {
+            let (cost, salt) = parse(expected).unwrap();
+            let hash = hash_with_salt(password, cost, &salt).unwrap();
+            assert_eq!(hash[4..], expected[4..]);
+            assert!(verify(password, expected).unwrap());
+            assert!(!verify("wrong", expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        let hash = hash("Piotr Pszczółkowski", MIN_COST).unwrap();
+        assert!(hash.starts_with("$2b$04$"));
+        assert_eq!(hash.len(), 60);
+        assert!(verify("Piotr Pszczółkowski", &hash).unwrap());
+        assert!(hash_with_salt("x", 3, &[0u8; SALT_SIZE]).is_err());
+        assert!(verify("x", "$3a$04$").is_err());
+    }
+}
@@ -1,116 +1,107 @@
 use crate::crypto::*;
+use crate::crypto::modes::Cts;
+use crate::crypto::kdf::{self, KdfParams};
 
 pub struct Blowfish {
     p: [u32; 18],
     s: [[u32; 256]; 4],
+    order: ByteOrder,
 }
 
 const BLOCK_SIZE: usize = 8;
+const MIN_KEY_SIZE: usize = 4;
+const MAX_KEY_SIZE: usize = 56;
 
 impl Blowfish {
     /// Creates blowfish-object for passed string|&str as key.
-    pub fn new_with_string<T: AsRef<str>>(key: T) -> Result<Blowfish, &'static str> {
+    pub fn new_with_string<T: AsRef<str>>(key: T) -> Result<Blowfish, CryptoError> {
         Blowfish::new(key.as_ref().as_bytes())
     }
 
+    /// Creates blowfish-object with key derived from password (maximal key length).
+    pub fn with_password(password: &[u8], salt: &[u8], params: &KdfParams) -> Result<Blowfish, CryptoError> {
+        let mut key = [0u8; MAX_KEY_SIZE];
+        let bf = kdf::derive_key(password, salt, params, &mut key).and_then(|_| Blowfish::new(&key));
+        zeroize(&mut key);
+        bf
+    }
+
     /// Creates blowfish-object for passed slice as key
-    pub fn new(key: &[u8]) -> Result<Blowfish, &'static str> {
+    pub fn new(key: &[u8]) -> Result<Blowfish, CryptoError> {
+        Blowfish::with_byte_order(key, ByteOrder::LittleEndian)
+    }
+
+    /// Creates blowfish-object reading blocks in passed byte order.
+    /// `ByteOrder::BigEndian` gives the same results as OpenSSL and
+    /// the reference implementation.
+    pub fn with_byte_order(key: &[u8], order: ByteOrder) -> Result<Blowfish, CryptoError> {
         let keylen = key.len();
-        if keylen < 4 || keylen > 56 {
-            return Err("invalid key length");
+        if !(MIN_KEY_SIZE..=MAX_KEY_SIZE).contains(&keylen) {
+            return Err(CryptoError::InvalidKeyLength { min: MIN_KEY_SIZE, max: MAX_KEY_SIZE, actual: keylen });
         }
 
-        let mut bf = Blowfish { p: [0u32; 18], s: [[0u32; 256]; 4] };
+        let mut bf = Blowfish::initial();
+        bf.order = order;
+        bf.expand_key(key, &[]);
+        Ok(bf)
+    }
 
-        for i in 0..4 {
-            for j in 0..256 {
-                bf.s[i][j] = ORIG_S[i][j];
-            }
-        }
+    /// Creates blowfish-object with initial (not keyed) P-array and S-boxes.
+    pub(crate) fn initial() -> Blowfish {
+        Blowfish { p: ORIG_P, s: ORIG_S, order: ByteOrder::LittleEndian }
+    }
 
+    /// Mixes key into P-array and S-boxes (key schedule).
+    /// Standard Blowfish uses empty salt; EksBlowfish (bcrypt)
+    /// XORs salt words into every block before encryption.
+    pub(crate) fn expand_key(&mut self, key: &[u8], salt: &[u8]) {
         let mut k = 0;
-        for i in 0..18 {
-            let mut data = 0u32;
-            for _j in 0..4 {
-                data = (data << 8) | (key[k] as u32);
-                k += 1;
-                if k >= keylen {
-                    k = 0;
-                }
-            }
-            bf.p[i] = ORIG_P[i] ^ data;
+        for p in self.p.iter_mut() {
+            *p ^= stream_word(key, &mut k);
         }
 
-        let mut xl = 0u32;
-        let mut xr = 0u32;
+        let mut j = 0;
+        let mut x = (0u32, 0u32);
 
         // P
-        let mut b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[0] = xl;
-        bf.p[1] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[2] = xl;
-        bf.p[3] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[4] = xl;
-        bf.p[5] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[6] = xl;
-        bf.p[7] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[8] = xl;
-        bf.p[9] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[10] = xl;
-        bf.p[11] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[12] = xl;
-        bf.p[13] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[14] = xl;
-        bf.p[15] = xr;
-        b = bf.encrypt(xl, xr);
-        xl = b.0;
-        xr = b.1;
-        bf.p[16] = xl;
-        bf.p[17] = xr;
+        for i in (0..18).step_by(2) {
+            if !salt.is_empty() {
+                x.0 ^= stream_word(salt, &mut j);
+                x.1 ^= stream_word(salt, &mut j);
+            }
+            x = self.encrypt(x.0, x.1);
+            self.p[i] = x.0;
+            self.p[i + 1] = x.1;
+        }
 
         // S
-        let mut i = 0;
-        while i < 4 {
-            let mut j = 0;
-            while j < 256 {
-                b = bf.encrypt(xl, xr);
-                xl = b.0;
-                xr = b.1;
-                bf.s[i][j] = xl;
-                bf.s[i][j + 1] = xr;
-                j += 2;
+        for i in 0..4 {
+            for k in (0..256).step_by(2) {
+                if !salt.is_empty() {
+                    x.0 ^= stream_word(salt, &mut j);
+                    x.1 ^= stream_word(salt, &mut j);
+                }
+                x = self.encrypt(x.0, x.1);
+                self.s[i][k] = x.0;
+                self.s[i][k + 1] = x.1;
             }
-            i += 1;
         }
-
-        Ok(bf)
+        zeroize(std::slice::from_mut(&mut x));
     }
 
     /// Heart of the algorithm.
-    fn f(&self, mut x: u32) -> u32 {
+    /// With `constant-time` feature S-boxes are read without secret indexes.
+    #[inline]
+    fn f(&self, x: u32) -> u32 {
+        if cfg!(feature = "constant-time") {
+            self.f_constant_time(x)
+        } else {
+            self.f_table(x)
+        }
+    }
+
+    /// Round function with direct S-box lookups (fast, leaks through cache timing).
+    fn f_table(&self, mut x: u32) -> u32 {
         let d = (x & 0xff) as usize;
         x = x.wrapping_shr(8);
         let c = (x & 0xff) as usize;
@@ -122,216 +113,268 @@ impl Blowfish {
         (self.s[0][a].wrapping_add(self.s[1][b]) ^ self.s[2][c]).wrapping_add(self.s[3][d])
     }
 
+    /// Round function scanning whole S-boxes with masks (cache-timing resistant).
+    fn f_constant_time(&self, x: u32) -> u32 {
+        let [a, b, c, d] = x.to_be_bytes().map(usize::from);
+        let s = |i: usize, idx: usize| ct_select(self.s[i].iter().copied(), idx);
+        (s(0, a).wrapping_add(s(1, b)) ^ s(2, c)).wrapping_add(s(3, d))
+    }
+
     /// Encrypts plain tuple (2xu32).
     /// Returns cipher tuple.
-    fn encrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
+    fn encrypt_tuple(&self, x: (u32, u32)) -> (u32, u32) {
         self.encrypt(x.0, x.1)
     }
 
     /// Encrypts two u32 words.
     /// Returns block (tuple of 2xu32).
     pub fn encrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
-        xl = xl ^ self.p[0];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[1];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[2];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[3];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[4];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[5];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[6];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[7];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[8];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[9];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[10];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[11];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[12];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[13];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[14];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[15];
-        xl = self.f(xr) ^ xl;
+        xl ^= self.p[0];
+        xr ^= self.f(xl);
+        xr ^= self.p[1];
+        xl ^= self.f(xr);
+        xl ^= self.p[2];
+        xr ^= self.f(xl);
+        xr ^= self.p[3];
+        xl ^= self.f(xr);
+        xl ^= self.p[4];
+        xr ^= self.f(xl);
+        xr ^= self.p[5];
+        xl ^= self.f(xr);
+        xl ^= self.p[6];
+        xr ^= self.f(xl);
+        xr ^= self.p[7];
+        xl ^= self.f(xr);
+        xl ^= self.p[8];
+        xr ^= self.f(xl);
+        xr ^= self.p[9];
+        xl ^= self.f(xr);
+        xl ^= self.p[10];
+        xr ^= self.f(xl);
+        xr ^= self.p[11];
+        xl ^= self.f(xr);
+        xl ^= self.p[12];
+        xr ^= self.f(xl);
+        xr ^= self.p[13];
+        xl ^= self.f(xr);
+        xl ^= self.p[14];
+        xr ^= self.f(xl);
+        xr ^= self.p[15];
+        xl ^= self.f(xr);
 
         ((xr ^ self.p[17]), (xl ^ self.p[16]))
     }
 
     /// Deccrypts cipher tuple (2xu32).
     /// Returns plain tuple.
-    fn decrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
+    fn decrypt_tuple(&self, x: (u32, u32)) -> (u32, u32) {
         self.decrypt(x.0, x.1)
     }
 
     /// Decrypts two u32 words.
     /// Returns block (tuple of 2xu32).
     pub fn decrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
-        xl = xl ^ self.p[17];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[16];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[15];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[14];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[13];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[12];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[11];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[10];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[9];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[8];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[7];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[6];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[5];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[4];
-        xl = self.f(xr) ^ xl;
-        xl = xl ^ self.p[3];
-        xr = self.f(xl) ^ xr;
-        xr = xr ^ self.p[2];
-        xl = self.f(xr) ^ xl;
+        xl ^= self.p[17];
+        xr ^= self.f(xl);
+        xr ^= self.p[16];
+        xl ^= self.f(xr);
+        xl ^= self.p[15];
+        xr ^= self.f(xl);
+        xr ^= self.p[14];
+        xl ^= self.f(xr);
+        xl ^= self.p[13];
+        xr ^= self.f(xl);
+        xr ^= self.p[12];
+        xl ^= self.f(xr);
+        xl ^= self.p[11];
+        xr ^= self.f(xl);
+        xr ^= self.p[10];
+        xl ^= self.f(xr);
+        xl ^= self.p[9];
+        xr ^= self.f(xl);
+        xr ^= self.p[8];
+        xl ^= self.f(xr);
+        xl ^= self.p[7];
+        xr ^= self.f(xl);
+        xr ^= self.p[6];
+        xl ^= self.f(xr);
+        xl ^= self.p[5];
+        xr ^= self.f(xl);
+        xr ^= self.p[4];
+        xl ^= self.f(xr);
+        xl ^= self.p[3];
+        xr ^= self.f(xl);
+        xr ^= self.p[2];
+        xl ^= self.f(xr);
 
         ((xr ^ self.p[0]), (xl ^ self.p[1]))
     }
 
     /// Encrypts passed plain text (ECB mode).
-    pub fn encrypt_ecb(&self, input: &Vec<u8>) -> Result<Vec<u8>, &'static str> {
-        if input.is_empty() {
-            return Err("No data to encrypt (ECB)");
-        }
+    pub fn encrypt_ecb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb(self, input)
+    }
 
-        let plain = {
-            let mut buffer = Vec::new();
-            buffer.extend(input);
-            let n = buffer.len() % BLOCK_SIZE;
-            if n != 0 {
-                buffer.extend(padding(BLOCK_SIZE - n));
-            }
-            buffer
-        };
-        let nbytes = plain.len();
+    /// Decrypts passsed cipher text (ECB mode).
+    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb(self, cipher)
+    }
 
+    /// Encrypts passed plain-text.
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc(self, input)
+    }
 
-        let mut cipher = Vec::with_capacity(nbytes);
-        cipher.resize(nbytes, 0);
+    /// Encrypts plain-text with passed IV vector.
+    pub fn encrypt_cbc_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_iv(self, input, iv)
+    }
 
-        let mut i: usize = 0;
-        while i < nbytes {
-            let mut x = bytes2block(&plain[i..]);
-            x = self.encrypt_block(x);
-            block2bytes(x, &mut cipher[i..]);
-            i += BLOCK_SIZE;
-        }
+    /// Decrypts passed cipher-text (CBC mode).
+    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc(self, cipher)
+    }
 
-        Ok(cipher)
+    /// Encrypts passed plain text with selected padding (ECB mode).
+    pub fn encrypt_ecb_with(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb_with(self, input, padding)
     }
 
-    /// Decrypts passsed cipher text (ECB mode).
-    pub fn decrypt_ecb(&self, cipher: &Vec<u8>) -> Result<Vec<u8>, &'static str> {
-        let nbytes = cipher.len();
-        if nbytes == 0 {
-            return Err("(ECB) no data to decrypt");
-        }
+    /// Decrypts passed cipher text and checks selected padding (ECB mode).
+    pub fn decrypt_ecb_with(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb_with(self, cipher, padding)
+    }
 
-        let mut plain = Vec::with_capacity(nbytes);
-        plain.resize(nbytes, 0);
+    /// Encrypts passed plain-text with selected padding (CBC mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc_with(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_with(self, input, padding)
+    }
 
-        let mut i: usize = 0;
-        let mut x: (u32, u32);
-        while i < nbytes {
-            x = bytes2block(&cipher[i..]);
-            x = self.decrypt_block(x);
-            block2bytes(x, &mut plain[i..]);
-            i += BLOCK_SIZE;
-        }
+    /// Encrypts plain-text with passed IV vector and selected padding (CBC mode).
+    pub fn encrypt_cbc_iv_with(&self, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_iv_with(self, input, iv, padding)
+    }
 
-        match padding_index(&plain) {
-            Some(idx) => Ok(plain[..idx].to_vec()),
-            _ => Ok(plain)
-        }
+    /// Decrypts passed cipher-text and checks selected padding (CBC mode).
+    pub fn decrypt_cbc_with(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc_with(self, cipher, padding)
     }
 
-    /// Encrypts passed plain-text.
+    /// Encrypts passed plain-text with ciphertext stealing (CBC mode).
     /// Before encryption creates IV vector.
-    pub fn encrypt_cbc(&self, input: &Vec<u8>) -> Result<Vec<u8>, &'static str> {
-        self.encrypt_cbc_iv(input, &random_bytes(BLOCK_SIZE))
+    pub fn encrypt_cbc_cts(&self, input: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_cts(self, input, variant)
     }
 
-    /// Encrypts plain-text with passed IV vector.
-    pub fn encrypt_cbc_iv(&self, input: &Vec<u8>, iv: &Vec<u8>) -> Result<Vec<u8>, &'static str> {
-        if iv.len() != BLOCK_SIZE {
-            return Err("(CBC) invalid IV vector length");
-        }
-        if input.is_empty() {
-            return Err("(CBC) nothing to encrypt");
-        }
+    /// Encrypts plain-text with passed IV vector and ciphertext stealing (CBC mode).
+    pub fn encrypt_cbc_cts_iv(&self, input: &[u8], iv: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_cts_iv(self, input, iv, variant)
+    }
 
-        // create plain-text bufer from input with padding if needed
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let nbytes = plain.len();
-        // create cipher-text buffer
-        // with IV vector as first block.
-        let mut cipher = zeroed_buffer(nbytes + BLOCK_SIZE);
-        cipher[0..BLOCK_SIZE].copy_from_slice(iv);
-
-        let mut i: usize = 0;
-        let mut x = bytes2block(&cipher[..]);
-        while i < nbytes {
-            let tmp = bytes2block(&plain[i..]);
-            x = self.encrypt(tmp.0 ^ x.0, tmp.1 ^ x.1);
-            block2bytes(x, &mut cipher[(i + BLOCK_SIZE)..]);
-            i += BLOCK_SIZE;
-        }
+    /// Decrypts passed cipher-text with ciphertext stealing (CBC mode).
+    pub fn decrypt_cbc_cts(&self, cipher: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc_cts(self, cipher, variant)
+    }
 
-        // OK
-        Ok(cipher)
+    /// Encrypts passed plain-text (CTR mode).
+    /// Before encryption creates random nonce.
+    pub fn encrypt_ctr(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ctr(self, input)
     }
 
-    pub fn decrypt_cbc(&self, cipher: &Vec<u8>) -> Result<Vec<u8>, &'static str> {
-        let nbytes = cipher.len();
-        if nbytes < (2 * BLOCK_SIZE) {
-            return Err("(CBC) cipher data invalid size");
-        }
+    /// Encrypts plain-text with passed nonce (CTR mode).
+    pub fn encrypt_ctr_nonce(&self, input: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ctr_nonce(self, input, nonce)
+    }
 
-        let mut plain: Vec<u8> = Vec::new();
-        plain.resize(nbytes - BLOCK_SIZE, 0);
-
-        let mut i = BLOCK_SIZE;
-        let mut p = bytes2block(&cipher[..]);
-        while i < nbytes {
-            let x = bytes2block(&cipher[i..]);
-            let tmp = x;
-            let c = self.decrypt_block(x);
-            words2bytes(c.0 ^ p.0, c.1 ^ p.1, &mut plain[(i - BLOCK_SIZE)..]);
-            p = tmp;
-            i += BLOCK_SIZE;
-        }
+    /// Decrypts passed cipher-text (CTR mode).
+    pub fn decrypt_ctr(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ctr(self, cipher)
+    }
+
+    /// Encrypts/decrypts data in place starting at byte `offset` of the key stream (CTR mode).
+    pub fn apply_ctr(&self, nonce: &[u8], offset: u64, data: &mut [u8]) -> Result<(), CryptoError> {
+        modes::apply_ctr(self, nonce, offset, data)
+    }
+
+    /// Encrypts passed plain-text (CFB mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cfb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cfb(self, input)
+    }
+
+    /// Encrypts plain-text with passed IV vector (CFB mode).
+    pub fn encrypt_cfb_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cfb_iv(self, input, iv)
+    }
+
+    /// Decrypts passed cipher-text (CFB mode).
+    pub fn decrypt_cfb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cfb(self, cipher)
+    }
+
+    /// Encrypts passed plain-text (OFB mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_ofb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ofb(self, input)
+    }
+
+    /// Encrypts plain-text with passed IV vector (OFB mode).
+    pub fn encrypt_ofb_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ofb_iv(self, input, iv)
+    }
 
-        match padding_index(&plain) {
-            Some(idx) => Ok(plain[..idx].to_vec()),
-            _ => Ok(plain)
+    /// Decrypts passed cipher-text (OFB mode).
+    pub fn decrypt_ofb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ofb(self, cipher)
+    }
+
+    /// Overwrites P-array and S-boxes with zeros (called on drop).
+    fn wipe(&mut self) {
+        zeroize(&mut self.p);
+        for s in self.s.iter_mut() {
+            zeroize(s);
         }
     }
 }
 
+/// Reads next 4 bytes (big-endian) from data treated as cyclic stream.
+fn stream_word(data: &[u8], pos: &mut usize) -> u32 {
+    let mut word = 0u32;
+    for _ in 0..4 {
+        word = (word << 8) | (data[*pos] as u32);
+        *pos = (*pos + 1) % data.len();
+    }
+    word
+}
+
+impl Drop for Blowfish {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+impl BlockCipher for Blowfish {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    type Block = [u8; BLOCK_SIZE];
+
+    fn new(key: &[u8]) -> Result<Blowfish, CryptoError> {
+        Blowfish::new(key)
+    }
+
+    fn encrypt_block(&self, block: &mut Self::Block) {
+        let x = self.encrypt_tuple(self.order.read_block(block));
+        self.order.write_block(x, block);
+    }
+
+    fn decrypt_block(&self, block: &mut Self::Block) {
+        let x = self.decrypt_tuple(self.order.read_block(block));
+        self.order.write_block(x, block);
+    }
+}
+
 /********************************************************************
 *                                                                   *
 *                            D A T A                                *
@@ -612,3 +655,98 @@ const ORIG_S: [[u32; 256]; 4] = [
         0xb74e6132, 0xce77e25b, 0x578fdfe3, 0x3ac372e6,
     ],
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::vectors::{hex, parse};
+    use crate::crypto::tests::sample_words;
+
+    /// Reference vectors use big-endian blocks,
+    /// by default this crate reads block words as little-endian.
+    fn words(bytes: &[u8]) -> (u32, u32) {
+        (u32::from_be_bytes(bytes[..4].try_into().unwrap()), u32::from_be_bytes(bytes[4..].try_into().unwrap()))
+    }
+
+    // key setup of every vector is slow with `constant-time` feature
+    #[test]
+    #[cfg_attr(feature = "constant-time", ignore)]
+    fn test_ecb_vectors() {
+        for v in parse(include_str!("testdata/blowfish_ecb.txt")) {
+            let bf = Blowfish::new(&hex(v[0])).unwrap();
+            let (plain, cipher) = (words(&hex(v[1])), words(&hex(v[2])));
+            assert_eq!(bf.encrypt_tuple(plain), cipher, "key {}", v[0]);
+            assert_eq!(bf.decrypt_tuple(cipher), plain, "key {}", v[0]);
+
+            let bf = Blowfish::with_byte_order(&hex(v[0]), ByteOrder::BigEndian).unwrap();
+            assert_eq!(bf.encrypt_ecb_with(&hex(v[1]), Padding::None).unwrap(), hex(v[2]), "key {}", v[0]);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "constant-time", ignore)]
+    fn test_set_key_vectors() {
+        for v in parse(include_str!("testdata/blowfish_set_key.txt")) {
+            let key = hex(v[0]);
+            let bf = if key.len() >= MIN_KEY_SIZE {
+                Blowfish::new(&key).unwrap()
+            } else {
+                // `new` rejects keys shorter than 32 bits, the key schedule doesn't care
+                let mut bf = Blowfish::initial();
+                bf.expand_key(&key, &[]);
+                bf
+            };
+            assert_eq!(bf.encrypt_tuple(words(&hex(v[1]))), words(&hex(v[2])), "key {}", v[0]);
+        }
+    }
+
+    #[test]
+    fn test_chain_vectors() {
+        for v in parse(include_str!("testdata/blowfish_chain.txt")) {
+            let bf = Blowfish::with_byte_order(&hex(v[1]), ByteOrder::BigEndian).unwrap();
+            let (iv, plain, cipher) = (hex(v[2]), hex(v[3]), hex(v[4]));
+            let encrypted = match v[0] {
+                "cbc" => bf.encrypt_cbc_iv_with(&plain, &iv, Padding::None).unwrap(),
+                "cfb" => bf.encrypt_cfb_iv(&plain, &iv).unwrap(),
+                "ofb" => bf.encrypt_ofb_iv(&plain, &iv).unwrap(),
+                mode => panic!("unknown mode {}", mode),
+            };
+            assert_eq!(encrypted[..BLOCK_SIZE], iv[..]);
+            assert_eq!(encrypted[BLOCK_SIZE..], cipher[..], "mode {}", v[0]);
+        }
+    }
+
+    #[test]
+    fn test_openssl() {
+        // openssl enc -bf-cbc -K 0123456789ABCDEFF0E1D2C3B4A59687 -iv FEDCBA9876543210
+        let bf = Blowfish::with_byte_order(&hex("0123456789ABCDEFF0E1D2C3B4A59687"), ByteOrder::BigEndian).unwrap();
+        let iv = hex("FEDCBA9876543210");
+        let cipher = hex("5e1762f619720c9c5e427c85fa3afe3a73923f959dea2d8b");
+        let encrypted = bf.encrypt_cbc_iv_with(b"Yamato & Musashi!", &iv, Padding::Pkcs7).unwrap();
+        assert_eq!(encrypted[BLOCK_SIZE..], cipher[..]);
+        assert_eq!(bf.decrypt_cbc_with(&encrypted, Padding::Pkcs7).unwrap(), b"Yamato & Musashi!");
+
+        // default byte order is not compatible
+        let bf = Blowfish::new(&hex("0123456789ABCDEFF0E1D2C3B4A59687")).unwrap();
+        assert_ne!(bf.encrypt_cbc_iv_with(b"Yamato & Musashi!", &iv, Padding::Pkcs7).unwrap(), encrypted);
+    }
+
+    /// F scanning the key-dependent S-boxes agrees with direct lookups,
+    /// this ties the masked path of `constant-time` to the table path checked by the vector tests.
+    #[test]
+    fn test_constant_time() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        for x in sample_words() {
+            assert_eq!(bf.f_constant_time(x), bf.f_table(x), "x = {:#010x}", x);
+        }
+    }
+
+    #[test]
+    fn test_wipe() {
+        let mut bf = Blowfish::new(b"TESTKEY").unwrap();
+        assert_ne!(bf.p, ORIG_P);
+        bf.wipe();
+        assert_eq!(bf.p, [0u32; 18]);
+        assert!(bf.s.iter().flatten().all(|&x| x == 0));
+    }
+}
@@ -0,0 +1,186 @@
+//! CMAC (OMAC1) message authentication code, NIST SP 800-38B.
+//!
+//! Works with any block cipher of 64 or 128 bit blocks.
+//! For 64 bit blocks subkeys are derived with Rb = 0x1B.
+
+use crate::crypto::*;
+
+/// Streaming CMAC generator.
+pub struct Cmac<'a, C: BlockCipher> {
+    cipher: &'a C,
+    k1: C::Block,
+    k2: C::Block,
+    state: C::Block,
+    buffer: C::Block,
+    filled: usize,
+}
+
+impl<'a, C: BlockCipher> Cmac<'a, C> {
+    /// Creates MAC generator, derives subkeys K1 and K2.
+    pub fn new(cipher: &'a C) -> Result<Cmac<'a, C>, CryptoError> {
+        let rb = match C::BLOCK_SIZE {
+            8 => 0x1b,
+            16 => 0x87,
+            _ => return Err(CryptoError::InvalidParameter("CMAC needs 64 or 128 bit blocks")),
+        };
+        let mut l = C::Block::default();
+        cipher.encrypt_block(&mut l);
+        let k1 = dbl::<C>(&l, rb);
+        let k2 = dbl::<C>(&k1, rb);
+        zeroize(l.as_mut());
+        Ok(Cmac { cipher, k1, k2, state: C::Block::default(), buffer: C::Block::default(), filled: 0 })
+    }
+
+    /// Returns subkeys K1 and K2.
+    pub fn subkeys(&self) -> (C::Block, C::Block) {
+        (self.k1, self.k2)
+    }
+
+    /// Adds passed data to the MAC.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // the last block is processed in finalize, so a full buffer waits for more data
+            if self.filled == C::BLOCK_SIZE {
+                xor_into(self.state.as_mut(), self.buffer.as_ref());
+                self.cipher.encrypt_block(&mut self.state);
+                self.filled = 0;
+            }
+            let n = (C::BLOCK_SIZE - self.filled).min(data.len());
+            self.buffer.as_mut()[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            data = &data[n..];
+        }
+    }
+
+    /// Returns the tag (one block).
+    pub fn finalize(mut self) -> Vec<u8> {
+        let key = if self.filled == C::BLOCK_SIZE {
+            self.k1
+        } else {
+            let buffer = self.buffer.as_mut();
+            buffer[self.filled] = 0x80;
+            buffer[self.filled + 1..].fill(0);
+            self.k2
+        };
+        xor_into(self.state.as_mut(), self.buffer.as_ref());
+        xor_into(self.state.as_mut(), key.as_ref());
+        self.cipher.encrypt_block(&mut self.state);
+        self.state.as_ref().to_vec()
+    }
+
+    /// Checks (in constant time) if passed tag matches the data.
+    /// Tag may be truncated, but not shorter than 4 bytes.
+    pub fn verify(self, tag: &[u8]) -> bool {
+        if tag.len() < 4 || tag.len() > C::BLOCK_SIZE {
+            return false;
+        }
+        constant_time_eq(&self.finalize()[..tag.len()], tag)
+    }
+}
+
+impl<C: BlockCipher> Drop for Cmac<'_, C> {
+    fn drop(&mut self) {
+        for block in [&mut self.k1, &mut self.k2, &mut self.state, &mut self.buffer] {
+            zeroize(block.as_mut());
+        }
+    }
+}
+
+/// Computes CMAC of passed data.
+pub fn mac<C: BlockCipher>(cipher: &C, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let mut cmac = Cmac::new(cipher)?;
+    cmac.update(data);
+    Ok(cmac.finalize())
+}
+
+/// Multiplies block by x in GF(2^n), bits are taken in big-endian order.
+fn dbl<C: BlockCipher>(block: &C::Block, rb: u8) -> C::Block {
+    let input = block.as_ref();
+    let mut output = C::Block::default();
+    let out = output.as_mut();
+    for i in 0..C::BLOCK_SIZE {
+        let next = if i + 1 < C::BLOCK_SIZE { input[i + 1] >> 7 } else { 0 };
+        out[i] = (input[i] << 1) | next;
+    }
+    // constant time: mask is 0xff when the most significant bit is set
+    let mask = 0u8.wrapping_sub(input[0] >> 7);
+    out[C::BLOCK_SIZE - 1] ^= rb & mask;
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::blowfish::Blowfish;
+    use crate::crypto::gost::{Gost, SBOX_TC26_Z};
+    use crate::crypto::kuznyechik::Kuznyechik;
+    use crate::crypto::vectors::hex;
+
+    #[test]
+    fn test_dbl() {
+        let block = [0x80u8, 0, 0, 0, 0, 0, 0, 1];
+        assert_eq!(dbl::<Blowfish>(&block, 0x1b), [0, 0, 0, 0, 0, 0, 0, 0x02 ^ 0x1b]);
+        let block = [0x40u8, 0, 0, 0, 0, 0, 0, 0x81];
+        assert_eq!(dbl::<Blowfish>(&block, 0x1b), [0x80, 0, 0, 0, 0, 0, 1, 0x02]);
+    }
+
+    #[test]
+    fn test_magma() {
+        // GOST R 34.13-2015, A.2.6
+        let key = hex("ffeeddccbbaa99887766554433221100f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let magma = Gost::with_sbox_and_order(&key, &SBOX_TC26_Z, ByteOrder::BigEndian).unwrap();
+        let cmac = Cmac::new(&magma).unwrap();
+        assert_eq!(cmac.subkeys(), (hex("5f459b3342521424")[..].try_into().unwrap(), hex("be8b366684a42848")[..].try_into().unwrap()));
+
+        let plain = hex("92def06b3c130a59db54c704f8189d204a98fb2e67a8024c8912409b17b57e41");
+        assert_eq!(mac(&magma, &plain).unwrap(), hex("154e72102030c5bb"));
+    }
+
+    #[test]
+    fn test_kuznyechik() {
+        // GOST R 34.13-2015, A.1.6 (the standard truncates MAC to 64 bits)
+        let key = hex("8899aabbccddeeff0011223344556677fedcba98765432100123456789abcdef");
+        let kz = Kuznyechik::new(&key).unwrap();
+        let cmac = Cmac::new(&kz).unwrap();
+        assert_eq!(cmac.subkeys(), (hex("297d82bc4d39e3ca0de0573298151dc7")[..].try_into().unwrap(), hex("52fb05789a73c7941bc0ae65302a3b8e")[..].try_into().unwrap()));
+
+        let plain = hex("1122334455667700ffeeddccbbaa998800112233445566778899aabbcceeff0a\
+                         112233445566778899aabbcceeff0a002233445566778899aabbcceeff0a0011");
+        assert_eq!(mac(&kz, &plain).unwrap()[..8], hex("336f4d296059fbe3")[..]);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let plain: Vec<u8> = (0..33u8).collect();
+        for nbytes in 0..=plain.len() {
+            let expected = mac(&bf, &plain[..nbytes]).unwrap();
+            for split in 0..=nbytes {
+                let mut cmac = Cmac::new(&bf).unwrap();
+                cmac.update(&plain[..split]);
+                cmac.update(&plain[split..nbytes]);
+                assert_eq!(cmac.finalize(), expected);
+            }
+        }
+        // padded and aligned messages differ
+        assert_ne!(mac(&bf, &plain[..7]).unwrap(), mac(&bf, &[&plain[..7], &[0x80]].concat()).unwrap());
+    }
+
+    #[test]
+    fn test_verify() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let tag = mac(&bf, b"config").unwrap();
+
+        let mut cmac = Cmac::new(&bf).unwrap();
+        cmac.update(b"config");
+        assert!(cmac.verify(&tag[..4]));
+
+        let mut cmac = Cmac::new(&bf).unwrap();
+        cmac.update(b"Config");
+        assert!(!cmac.verify(&tag));
+
+        let mut cmac = Cmac::new(&bf).unwrap();
+        cmac.update(b"config");
+        assert!(!cmac.verify(&tag[..3]));
+    }
+}
@@ -0,0 +1,432 @@
+//! Self-describing encrypted container.
+//!
+//! Layout (integers are big-endian):
+//! ```text
+//! magic       4 bytes   "RSCF"
+//! version     1 byte    1
+//! algorithm   1 byte    1 = Blowfish, 2 = Gost
+//! mode        1 byte    1 = CBC, 2 = CTR
+//! padding     1 byte    0 = none, 1 = ISO 7816-4, 2 = PKCS#7, 3 = ANSI X9.23, 4 = zero
+//! kdf         1 byte    0 = none (raw keys)
+//!                       1 = PBKDF2-HMAC-SHA256: salt (1 byte length + bytes), iterations (4 bytes)
+//!                       2 = scrypt: salt (1 byte length + bytes), log_n (1 byte), r (4 bytes), p (4 bytes)
+//! iv          1 byte length + bytes
+//! cipher text 8 bytes length + bytes
+//! tag         8 bytes   GOST 28147-89 imitovstavka of all previous bytes
+//! ```
+//! Everything needed to decrypt (except keys) is read from the header,
+//! so `unpack` picks the right cipher and mode automatically.
+//! The header is not trusted before the tag is checked: KDF parameters
+//! above the limits of `KdfParams::validate` are rejected before derivation.
+
+use crate::crypto::*;
+use crate::crypto::blowfish::Blowfish;
+use crate::crypto::gost::{Gost, GostMac};
+use crate::crypto::kdf::{self, KdfParams};
+
+const MAGIC: &[u8; 4] = b"RSCF";
+const VERSION: u8 = 1;
+const TAG_SIZE: usize = 8;
+const SALT_SIZE: usize = 16;
+// size of keys derived from password
+const BLOWFISH_KEY_SIZE: usize = 56;
+const GOST_KEY_SIZE: usize = 32;
+const MAC_KEY_SIZE: usize = 32;
+
+/// Cipher used to encrypt the container.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Blowfish = 1,
+    Gost = 2,
+}
+
+/// Mode of operation used to encrypt the container.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Cbc = 1,
+    Ctr = 2,
+}
+
+/// Key derivation used to create the keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    None,
+    Password { salt: Vec<u8>, params: KdfParams },
+}
+
+/// Parameters read from the container's header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub mode: Mode,
+    pub padding: Padding,
+    pub kdf: Kdf,
+    pub iv: Vec<u8>,
+}
+
+impl Algorithm {
+    fn from_byte(v: u8) -> Result<Algorithm, CryptoError> {
+        match v {
+            1 => Ok(Algorithm::Blowfish),
+            2 => Ok(Algorithm::Gost),
+            _ => Err(CryptoError::InvalidFormat("unknown algorithm")),
+        }
+    }
+
+    /// Block size of the cipher (size of IV vector).
+    fn block_size(&self) -> usize {
+        match self {
+            Algorithm::Blowfish => <Blowfish as BlockCipher>::BLOCK_SIZE,
+            Algorithm::Gost => <Gost as BlockCipher>::BLOCK_SIZE,
+        }
+    }
+}
+
+impl Mode {
+    fn from_byte(v: u8) -> Result<Mode, CryptoError> {
+        match v {
+            1 => Ok(Mode::Cbc),
+            2 => Ok(Mode::Ctr),
+            _ => Err(CryptoError::InvalidFormat("unknown mode")),
+        }
+    }
+
+    /// Padding used when the container is created.
+    fn padding(&self) -> Padding {
+        match self {
+            Mode::Cbc => Padding::Iso7816,
+            Mode::Ctr => Padding::None,
+        }
+    }
+}
+
+fn padding_from_byte(v: u8) -> Result<Padding, CryptoError> {
+    match v {
+        0 => Ok(Padding::None),
+        1 => Ok(Padding::Iso7816),
+        2 => Ok(Padding::Pkcs7),
+        3 => Ok(Padding::AnsiX923),
+        4 => Ok(Padding::Zero),
+        _ => Err(CryptoError::InvalidFormat("unknown padding")),
+    }
+}
+
+fn padding_to_byte(padding: Padding) -> u8 {
+    match padding {
+        Padding::None => 0,
+        Padding::Iso7816 => 1,
+        Padding::Pkcs7 => 2,
+        Padding::AnsiX923 => 3,
+        Padding::Zero => 4,
+    }
+}
+
+impl Kdf {
+    fn read(data: &[u8], pos: &mut usize) -> Result<Kdf, CryptoError> {
+        let id = read_u8(data, pos)?;
+        if id == 0 {
+            return Ok(Kdf::None);
+        }
+        let nbytes = read_u8(data, pos)? as usize;
+        let salt = read_bytes(data, pos, nbytes)?.to_vec();
+        let params = match id {
+            1 => KdfParams::Pbkdf2 { iterations: read_u32(data, pos)? },
+            2 => KdfParams::Scrypt { log_n: read_u8(data, pos)?, r: read_u32(data, pos)?, p: read_u32(data, pos)? },
+            _ => return Err(CryptoError::InvalidFormat("unknown key derivation")),
+        };
+        // parameters come from unauthenticated data, the tag is checked after derivation
+        params.validate()?;
+        Ok(Kdf::Password { salt, params })
+    }
+
+    fn write(&self, data: &mut Vec<u8>) -> Result<(), CryptoError> {
+        match self {
+            Kdf::None => data.push(0),
+            Kdf::Password { salt, params } => {
+                let id = match params {
+                    KdfParams::Pbkdf2 { .. } => 1,
+                    KdfParams::Scrypt { .. } => 2,
+                };
+                let nbytes = u8::try_from(salt.len()).map_err(|_| CryptoError::InvalidParameter("salt is longer than 255 bytes"))?;
+                data.push(id);
+                data.push(nbytes);
+                data.extend_from_slice(salt);
+                match *params {
+                    KdfParams::Pbkdf2 { iterations } => data.extend_from_slice(&iterations.to_be_bytes()),
+                    KdfParams::Scrypt { log_n, r, p } => {
+                        data.push(log_n);
+                        data.extend_from_slice(&r.to_be_bytes());
+                        data.extend_from_slice(&p.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Derives cipher's key and MAC key from password.
+    fn derive(&self, algorithm: Algorithm, password: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        match self {
+            Kdf::None => Err(CryptoError::InvalidParameter("container keys are not derived from password")),
+            Kdf::Password { salt, params } => {
+                let nbytes = match algorithm {
+                    Algorithm::Blowfish => BLOWFISH_KEY_SIZE,
+                    Algorithm::Gost => GOST_KEY_SIZE,
+                };
+                let mut keys = vec![0u8; nbytes + MAC_KEY_SIZE];
+                kdf::derive_key(password, salt, params, &mut keys)?;
+                let mac_key = keys.split_off(nbytes);
+                Ok((keys, mac_key))
+            }
+        }
+    }
+}
+
+/// Encrypts plain text and packs it into the container.
+/// `mac_key` (32 bytes) is used for the trailing imitovstavka.
+pub fn pack(algorithm: Algorithm, mode: Mode, key: &[u8], mac_key: &[u8], plain: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    pack_with_kdf(algorithm, mode, Kdf::None, key, mac_key, plain)
+}
+
+/// Encrypts plain text with keys derived from password and packs it into the container.
+/// Salt is random, KDF parameters are saved in the header.
+pub fn pack_with_password(algorithm: Algorithm, mode: Mode, password: &[u8], params: KdfParams, plain: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let kdf = Kdf::Password { salt: random_bytes(SALT_SIZE), params };
+    let (key, mac_key) = kdf.derive(algorithm, password)?;
+    pack_with_kdf(algorithm, mode, kdf, &key, &mac_key, plain)
+}
+
+/// Checks the tag and decrypts the container.
+/// Cipher and mode are taken from the header.
+pub fn unpack(key: &[u8], mac_key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let (header, offset) = read_header(data)?;
+    unpack_with_header(&header, offset, key, mac_key, data)
+}
+
+/// Derives keys from password (KDF parameters are taken from the header),
+/// checks the tag and decrypts the container.
+pub fn unpack_with_password(password: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let (header, offset) = read_header(data)?;
+    let (key, mac_key) = header.kdf.derive(header.algorithm, password)?;
+    unpack_with_header(&header, offset, &key, &mac_key, data)
+}
+
+fn unpack_with_header(header: &Header, offset: usize, key: &[u8], mac_key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let body = verify(mac_key, data)?;
+
+    let mut pos = offset;
+    let nbytes = read_u64(body, &mut pos)? as usize;
+    if body.len() - pos != nbytes {
+        return Err(CryptoError::InvalidFormat("size of cipher text"));
+    }
+    let mut input = header.iv.clone();
+    input.extend_from_slice(&body[pos..]);
+
+    match header.algorithm {
+        Algorithm::Blowfish => decrypt(&Blowfish::new(key)?, header, &input),
+        Algorithm::Gost => decrypt(&Gost::new(key)?, header, &input),
+    }
+}
+
+/// Reads header of the container.
+/// Returns header and offset of the cipher text length field.
+pub fn read_header(data: &[u8]) -> Result<(Header, usize), CryptoError> {
+    if data.len() < MAGIC.len() + 6 || &data[..MAGIC.len()] != MAGIC {
+        return Err(CryptoError::InvalidFormat("container magic"));
+    }
+    let mut pos = MAGIC.len();
+    let version = read_u8(data, &mut pos)?;
+    if version != VERSION {
+        return Err(CryptoError::InvalidFormat("unsupported container version"));
+    }
+    let algorithm = Algorithm::from_byte(read_u8(data, &mut pos)?)?;
+    let mode = Mode::from_byte(read_u8(data, &mut pos)?)?;
+    let padding = padding_from_byte(read_u8(data, &mut pos)?)?;
+    if (mode == Mode::Ctr) != (padding == Padding::None) {
+        return Err(CryptoError::InvalidFormat("padding does not match mode"));
+    }
+    let kdf = Kdf::read(data, &mut pos)?;
+    let nbytes = read_u8(data, &mut pos)? as usize;
+    if nbytes != algorithm.block_size() {
+        return Err(CryptoError::InvalidIv { expected: algorithm.block_size(), actual: nbytes });
+    }
+    let iv = read_bytes(data, &mut pos, nbytes)?.to_vec();
+
+    Ok((Header { version, algorithm, mode, padding, kdf, iv }, pos))
+}
+
+fn pack_with_kdf(algorithm: Algorithm, mode: Mode, kdf: Kdf, key: &[u8], mac_key: &[u8], plain: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let mac = Gost::new(mac_key)?;
+    let encrypted = match algorithm {
+        Algorithm::Blowfish => encrypt(&Blowfish::new(key)?, mode, plain)?,
+        Algorithm::Gost => encrypt(&Gost::new(key)?, mode, plain)?,
+    };
+    // every supported cipher has 8 byte blocks
+    let (iv, cipher) = encrypted.split_at(8);
+
+    let mut data = Vec::with_capacity(encrypted.len() + 32);
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+    data.push(algorithm as u8);
+    data.push(mode as u8);
+    data.push(padding_to_byte(mode.padding()));
+    kdf.write(&mut data)?;
+    data.push(iv.len() as u8);
+    data.extend_from_slice(iv);
+    data.extend_from_slice(&(cipher.len() as u64).to_be_bytes());
+    data.extend_from_slice(cipher);
+
+    let mut imit = GostMac::with_tag_size(&mac, TAG_SIZE)?;
+    imit.update(&data);
+    data.extend(imit.finalize());
+    Ok(data)
+}
+
+/// Checks the trailing tag, returns data without the tag.
+fn verify<'d>(mac_key: &[u8], data: &'d [u8]) -> Result<&'d [u8], CryptoError> {
+    if data.len() < TAG_SIZE {
+        return Err(CryptoError::InvalidFormat("container is truncated"));
+    }
+    let (body, tag) = data.split_at(data.len() - TAG_SIZE);
+    let mac = Gost::new(mac_key)?;
+    let mut imit = GostMac::with_tag_size(&mac, TAG_SIZE)?;
+    imit.update(body);
+    if !imit.verify(tag) {
+        return Err(CryptoError::AuthenticationFailed);
+    }
+    Ok(body)
+}
+
+fn encrypt<C: BlockCipher>(cipher: &C, mode: Mode, plain: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    match mode {
+        Mode::Cbc => modes::encrypt_cbc_with(cipher, plain, mode.padding()),
+        Mode::Ctr => modes::encrypt_ctr(cipher, plain),
+    }
+}
+
+fn decrypt<C: BlockCipher>(cipher: &C, header: &Header, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    match header.mode {
+        Mode::Cbc => modes::decrypt_cbc_with(cipher, data, header.padding),
+        Mode::Ctr => modes::decrypt_ctr(cipher, data),
+    }
+}
+
+fn read_bytes<'d>(data: &'d [u8], pos: &mut usize, nbytes: usize) -> Result<&'d [u8], CryptoError> {
+    if data.len() < *pos + nbytes {
+        return Err(CryptoError::InvalidFormat("container is truncated"));
+    }
+    let bytes = &data[*pos..*pos + nbytes];
+    *pos += nbytes;
+    Ok(bytes)
+}
+
+fn read_u8(data: &[u8], pos: &mut usize) -> Result<u8, CryptoError> {
+    Ok(read_bytes(data, pos, 1)?[0])
+}
+
+fn read_u32(data: &[u8], pos: &mut usize) -> Result<u32, CryptoError> {
+    let mut buffer = [0u8; 4];
+    buffer.copy_from_slice(read_bytes(data, pos, 4)?);
+    Ok(u32::from_be_bytes(buffer))
+}
+
+fn read_u64(data: &[u8], pos: &mut usize) -> Result<u64, CryptoError> {
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(read_bytes(data, pos, 8)?);
+    Ok(u64::from_be_bytes(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8; 32] = b"12345678901234567890123456789012";
+    const MAC_KEY: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz012345";
+
+    #[test]
+    fn test_round_trip() {
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+        for algorithm in [Algorithm::Blowfish, Algorithm::Gost] {
+            for mode in [Mode::Cbc, Mode::Ctr] {
+                let data = pack(algorithm, mode, KEY, MAC_KEY, plain).unwrap();
+                let (header, _) = read_header(&data).unwrap();
+                assert_eq!(header.algorithm, algorithm);
+                assert_eq!(header.mode, mode);
+                assert_eq!(header.kdf, Kdf::None);
+                assert_eq!(unpack(KEY, MAC_KEY, &data).unwrap(), plain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tampered() {
+        let plain = "Yamato & Musashi".as_bytes();
+        let data = pack(Algorithm::Gost, Mode::Ctr, KEY, MAC_KEY, plain).unwrap();
+
+        for i in [0usize, 6, 12, data.len() - 10, data.len() - 1] {
+            let mut corrupted = data.clone();
+            corrupted[i] ^= 0x01;
+            assert!(unpack(KEY, MAC_KEY, &corrupted).is_err());
+        }
+        assert!(unpack(KEY, KEY, &data).is_err());
+        assert!(unpack(KEY, MAC_KEY, &data[..data.len() - 1]).is_err());
+    }
+
+    /// Header with passed key derivation and size of IV vector, empty cipher text and dummy tag.
+    fn hostile(kdf: Kdf, iv_size: usize) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[VERSION, Algorithm::Gost as u8, Mode::Ctr as u8, 0]);
+        kdf.write(&mut data).unwrap();
+        data.push(iv_size as u8);
+        data.resize(data.len() + iv_size + 8 + TAG_SIZE, 0);
+        data
+    }
+
+    #[test]
+    fn test_hostile_header() {
+        let salt = vec![0u8; SALT_SIZE];
+        for params in [
+            KdfParams::Pbkdf2 { iterations: u32::MAX },
+            KdfParams::Scrypt { log_n: 31, r: 8, p: 1 },
+            KdfParams::Scrypt { log_n: 16, r: 1 << 30, p: 1 },
+            KdfParams::Scrypt { log_n: 16, r: 1 << 15, p: 1 << 15 },
+            KdfParams::Scrypt { log_n: 20, r: 1024, p: 1 },
+        ] {
+            let data = hostile(Kdf::Password { salt: salt.clone(), params }, 8);
+            assert!(matches!(read_header(&data), Err(CryptoError::InvalidParameter(_))), "{:?}", params);
+            assert!(matches!(unpack_with_password(b"secret", &data), Err(CryptoError::InvalidParameter(_))), "{:?}", params);
+        }
+
+        let kdf = Kdf::Password { salt: salt.clone(), params: KdfParams::Pbkdf2 { iterations: 1 } };
+        for iv_size in [0usize, 7, 16, 255] {
+            let data = hostile(kdf.clone(), iv_size);
+            assert_eq!(read_header(&data).err(), Some(CryptoError::InvalidIv { expected: 8, actual: iv_size }));
+        }
+        assert!(read_header(&hostile(kdf, 8)).is_ok());
+
+        let kdf = Kdf::Password { salt: vec![0u8; 256], params: KdfParams::Pbkdf2 { iterations: 1 } };
+        assert!(matches!(kdf.write(&mut Vec::new()), Err(CryptoError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_password() {
+        let plain = "Adam, Artur, Błażej, Kacpi, Nikoś, XY".as_bytes();
+        let params = [KdfParams::Pbkdf2 { iterations: 100 }, KdfParams::Scrypt { log_n: 4, r: 2, p: 1 }];
+        for params in params {
+            for algorithm in [Algorithm::Blowfish, Algorithm::Gost] {
+                let data = pack_with_password(algorithm, Mode::Cbc, b"secret", params, plain).unwrap();
+                let (header, _) = read_header(&data).unwrap();
+                match header.kdf {
+                    Kdf::Password { ref salt, params: p } => {
+                        assert_eq!(salt.len(), SALT_SIZE);
+                        assert_eq!(p, params);
+                    }
+                    Kdf::None => panic!("KDF parameters are missing"),
+                }
+                assert_eq!(unpack_with_password(b"secret", &data).unwrap(), plain);
+                assert!(unpack_with_password(b"Secret", &data).is_err());
+            }
+        }
+    }
+}
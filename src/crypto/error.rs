@@ -0,0 +1,57 @@
+use std::fmt;
+
+/// Errors returned by the crypto module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CryptoError {
+    /// Key length is out of the range accepted by the cipher.
+    InvalidKeyLength { min: usize, max: usize, actual: usize },
+    /// IV vector (nonce) has invalid length.
+    InvalidIv { expected: usize, actual: usize },
+    /// Nothing to encrypt/decrypt.
+    EmptyInput,
+    /// Padding of decrypted data is malformed.
+    BadPadding,
+    /// Cipher text is too short or is not a multiple of the block size.
+    TruncatedCiphertext { block_size: usize, actual: usize },
+    /// Invalid parameter (S-box, tag size, cost, KDF parameters...).
+    InvalidParameter(&'static str),
+    /// Data is not in expected format (container, hash string...).
+    InvalidFormat(&'static str),
+    /// Authentication tag does not match the data.
+    AuthenticationFailed,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::InvalidKeyLength { min, max, actual } if min == max =>
+                write!(f, "invalid key length: expected {} bytes, got {}", min, actual),
+            CryptoError::InvalidKeyLength { min, max, actual } =>
+                write!(f, "invalid key length: expected {}..={} bytes, got {}", min, max, actual),
+            CryptoError::InvalidIv { expected, actual } =>
+                write!(f, "invalid size of IV vector: expected {} bytes, got {}", expected, actual),
+            CryptoError::EmptyInput => write!(f, "nothing to process"),
+            CryptoError::BadPadding => write!(f, "invalid padding"),
+            CryptoError::TruncatedCiphertext { block_size, actual } =>
+                write!(f, "truncated cipher text: {} bytes (block size {})", actual, block_size),
+            CryptoError::InvalidParameter(text) => write!(f, "invalid parameter: {}", text),
+            CryptoError::InvalidFormat(text) => write!(f, "invalid format: {}", text),
+            CryptoError::AuthenticationFailed => write!(f, "authentication failed"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+impl From<CryptoError> for std::io::Error {
+    fn from(e: CryptoError) -> Self {
+        let kind = match e {
+            CryptoError::InvalidKeyLength { .. }
+            | CryptoError::InvalidIv { .. }
+            | CryptoError::InvalidParameter(_)
+            | CryptoError::EmptyInput => std::io::ErrorKind::InvalidInput,
+            _ => std::io::ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
@@ -1,4 +1,6 @@
 use crate::crypto::*;
+use crate::crypto::modes::Cts;
+use crate::crypto::kdf::{self, KdfParams};
 
 const BLOCK_SIZE: usize = 8;
 // 8 bytes = 2 u32 = 54 bit
@@ -12,6 +14,102 @@ const K4: [u8; 16] = [2, 12, 4, 1, 7, 10, 11, 6, 8, 5, 3, 15, 13, 0, 14, 9];
 const K3: [u8; 16] = [12, 1, 10, 15, 9, 2, 6, 8, 0, 13, 3, 4, 14, 7, 5, 11];
 const K2: [u8; 16] = [4, 11, 2, 14, 15, 0, 8, 13, 3, 12, 9, 7, 5, 10, 6, 1];
 const K1: [u8; 16] = [13, 2, 8, 4, 6, 15, 11, 1, 10, 9, 3, 14, 5, 0, 12, 7];
+// gamming constants (GOST 28147-89, 3.1)
+const C1: u32 = 0x01010104;
+const C2: u32 = 0x01010101;
+// default size of imitovstavka (bytes)
+const MAC_SIZE: usize = 4;
+
+/// Set of eight S-boxes.
+/// sbox[0] (K1) substitutes the lowest 4 bits, sbox[7] (K8) the highest.
+pub type SBox = [[u8; 16]; 8];
+
+/// Lookup tables used by the round function.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Tables {
+    /// Four 256-entry byte tables (1 KiB), every round assembles
+    /// and rotates the substituted word (low-memory option).
+    Bytes,
+    /// Four 256-entry u32 tables (additional 4 KiB) with the rotation
+    /// folded in, as in reference implementations (faster).
+    #[default]
+    Words,
+}
+
+/// S-boxes used by this crate since the beginning (default).
+pub const SBOX_DEFAULT: SBox = [K1, K2, K3, K4, K5, K6, K7, K8];
+
+/// id-GostR3411-94-TestParamSet (RFC 4357), used in RFC 5830/5831 examples.
+pub const SBOX_TEST_PARAMSET: SBox = [
+    [4, 10, 9, 2, 13, 8, 0, 14, 6, 11, 1, 12, 7, 15, 5, 3],
+    [14, 11, 4, 12, 6, 13, 15, 10, 2, 3, 8, 1, 0, 7, 5, 9],
+    [5, 8, 1, 13, 10, 3, 4, 2, 14, 15, 12, 7, 6, 0, 9, 11],
+    [7, 13, 10, 1, 0, 8, 9, 15, 14, 4, 6, 12, 11, 2, 5, 3],
+    [6, 12, 7, 1, 5, 15, 13, 8, 4, 10, 9, 14, 0, 3, 11, 2],
+    [4, 11, 10, 0, 7, 2, 1, 13, 3, 6, 8, 5, 9, 12, 15, 14],
+    [13, 11, 4, 1, 3, 15, 5, 9, 0, 10, 14, 7, 6, 8, 2, 12],
+    [1, 15, 13, 0, 5, 7, 10, 4, 9, 2, 3, 14, 6, 11, 8, 12],
+];
+
+/// id-Gost28147-89-CryptoPro-A-ParamSet (RFC 4357).
+pub const SBOX_CRYPTOPRO_A: SBox = [
+    [9, 6, 3, 2, 8, 11, 1, 7, 10, 4, 14, 15, 12, 0, 13, 5],
+    [3, 7, 14, 9, 8, 10, 15, 0, 5, 2, 6, 12, 11, 4, 13, 1],
+    [14, 4, 6, 2, 11, 3, 13, 8, 12, 15, 5, 10, 0, 7, 1, 9],
+    [14, 7, 10, 12, 13, 1, 3, 9, 0, 2, 11, 4, 15, 8, 5, 6],
+    [11, 5, 1, 9, 8, 13, 15, 0, 14, 4, 2, 3, 12, 7, 10, 6],
+    [3, 10, 13, 12, 1, 2, 0, 11, 7, 5, 9, 4, 8, 15, 14, 6],
+    [1, 13, 2, 9, 7, 10, 6, 0, 8, 12, 4, 5, 15, 3, 11, 14],
+    [11, 10, 15, 5, 0, 12, 14, 8, 6, 2, 3, 9, 1, 7, 13, 4],
+];
+
+/// id-Gost28147-89-CryptoPro-B-ParamSet (RFC 4357).
+pub const SBOX_CRYPTOPRO_B: SBox = [
+    [8, 4, 11, 1, 3, 5, 0, 9, 2, 14, 10, 12, 13, 6, 7, 15],
+    [0, 1, 2, 10, 4, 13, 5, 12, 9, 7, 3, 15, 11, 8, 6, 14],
+    [14, 12, 0, 10, 9, 2, 13, 11, 7, 5, 8, 15, 3, 6, 1, 4],
+    [7, 5, 0, 13, 11, 6, 1, 2, 3, 10, 12, 15, 4, 14, 9, 8],
+    [2, 7, 12, 15, 9, 5, 10, 11, 1, 4, 0, 13, 6, 8, 14, 3],
+    [8, 3, 2, 6, 4, 13, 14, 11, 12, 1, 7, 15, 10, 0, 9, 5],
+    [5, 2, 10, 11, 9, 1, 12, 3, 7, 4, 13, 0, 6, 15, 8, 14],
+    [0, 4, 11, 14, 8, 3, 7, 1, 10, 2, 9, 6, 15, 13, 5, 12],
+];
+
+/// id-Gost28147-89-CryptoPro-C-ParamSet (RFC 4357).
+pub const SBOX_CRYPTOPRO_C: SBox = [
+    [1, 11, 12, 2, 9, 13, 0, 15, 4, 5, 8, 14, 10, 7, 6, 3],
+    [0, 1, 7, 13, 11, 4, 5, 2, 8, 14, 15, 12, 9, 10, 6, 3],
+    [8, 2, 5, 0, 4, 9, 15, 10, 3, 7, 12, 13, 6, 14, 1, 11],
+    [3, 6, 0, 1, 5, 13, 10, 8, 11, 2, 9, 7, 14, 15, 12, 4],
+    [8, 13, 11, 0, 4, 5, 1, 2, 9, 3, 12, 14, 6, 15, 10, 7],
+    [12, 9, 11, 1, 8, 14, 2, 4, 7, 3, 6, 5, 10, 0, 15, 13],
+    [10, 9, 6, 8, 13, 14, 2, 0, 15, 3, 5, 11, 4, 1, 12, 7],
+    [7, 4, 0, 5, 10, 2, 15, 14, 12, 6, 1, 11, 13, 9, 3, 8],
+];
+
+/// id-Gost28147-89-CryptoPro-D-ParamSet (RFC 4357).
+pub const SBOX_CRYPTOPRO_D: SBox = [
+    [15, 12, 2, 10, 6, 4, 5, 0, 7, 9, 14, 13, 1, 11, 8, 3],
+    [11, 6, 3, 4, 12, 15, 14, 2, 7, 13, 8, 0, 5, 10, 9, 1],
+    [1, 12, 11, 0, 15, 14, 6, 5, 10, 13, 4, 8, 9, 3, 7, 2],
+    [1, 5, 14, 12, 10, 7, 0, 13, 6, 2, 11, 4, 9, 3, 15, 8],
+    [0, 12, 8, 9, 13, 2, 10, 11, 7, 3, 6, 5, 4, 14, 15, 1],
+    [8, 0, 15, 3, 2, 5, 14, 11, 1, 10, 4, 7, 12, 9, 13, 6],
+    [3, 0, 6, 15, 1, 14, 9, 2, 13, 8, 12, 4, 11, 10, 5, 7],
+    [1, 10, 6, 8, 15, 11, 0, 4, 12, 3, 5, 9, 7, 13, 2, 14],
+];
+
+/// id-tc26-gost-28147-param-Z (RFC 7836), the S-box of GOST R 34.12-2015 "Magma".
+pub const SBOX_TC26_Z: SBox = [
+    [12, 4, 6, 2, 10, 5, 11, 9, 14, 8, 13, 7, 0, 3, 15, 1],
+    [6, 8, 2, 3, 9, 10, 5, 12, 1, 14, 4, 7, 11, 13, 0, 15],
+    [11, 3, 5, 8, 2, 15, 10, 13, 14, 1, 7, 4, 12, 9, 6, 0],
+    [12, 8, 2, 1, 13, 4, 15, 6, 7, 0, 10, 5, 3, 14, 9, 11],
+    [7, 15, 5, 10, 8, 1, 6, 13, 0, 9, 3, 14, 11, 4, 2, 12],
+    [5, 13, 15, 6, 9, 2, 12, 10, 11, 7, 8, 1, 4, 3, 14, 0],
+    [8, 14, 2, 5, 6, 9, 1, 12, 15, 4, 11, 0, 13, 10, 3, 7],
+    [1, 7, 14, 13, 0, 5, 8, 3, 4, 15, 10, 6, 9, 12, 11, 2],
+];
 
 pub struct Gost {
     // k: [u32; 8],
@@ -27,38 +125,77 @@ pub struct Gost {
     k65: [u8; 256],
     k43: [u8; 256],
     k21: [u8; 256],
+    // k87..k21 expanded to words and rotated (None for Tables::Bytes)
+    words: Option<Box<[[u32; 256]; 4]>>,
+    order: ByteOrder,
 }
 
 impl Gost {
     /// Creates gost-object for passed string|&str as key.
-    pub fn with_key_as_text<T: AsRef<str>>(key: T) -> Result<Gost, &'static str> {
+    pub fn with_key_as_text<T: AsRef<str>>(key: T) -> Result<Gost, CryptoError> {
         Gost::new(key.as_ref().as_bytes())
     }
 
-    pub fn new(key: &[u8]) -> Result<Gost, &'static str> {
+    /// Creates gost-object with key derived from password.
+    pub fn with_password(password: &[u8], salt: &[u8], params: &KdfParams) -> Result<Gost, CryptoError> {
+        let mut key = [0u8; KEY_SIZE];
+        let gost = kdf::derive_key(password, salt, params, &mut key).and_then(|_| Gost::new(&key));
+        zeroize(&mut key);
+        gost
+    }
+
+    /// Creates gost-object for passed key with default S-boxes.
+    pub fn new(key: &[u8]) -> Result<Gost, CryptoError> {
+        Gost::with_sbox(key, &SBOX_DEFAULT)
+    }
+
+    /// Creates gost-object for passed key and S-boxes.
+    /// Every S-box must be a permutation of values 0..16.
+    pub fn with_sbox(key: &[u8], sbox: &SBox) -> Result<Gost, CryptoError> {
+        Gost::with_sbox_and_order(key, sbox, ByteOrder::LittleEndian)
+    }
+
+    /// Creates gost-object for passed key and S-boxes, key and blocks
+    /// are read in passed byte order.
+    /// With `ByteOrder::BigEndian` key and blocks are big-endian numbers,
+    /// as in GOST R 34.12-2015 (Magma), so N1 is the second word of a block.
+    pub fn with_sbox_and_order(key: &[u8], sbox: &SBox, order: ByteOrder) -> Result<Gost, CryptoError> {
+        Gost::with_tables(key, sbox, order, Tables::Words)
+    }
+
+    /// Creates gost-object for passed key, S-boxes, byte order and type of lookup tables.
+    pub fn with_tables(key: &[u8], sbox: &SBox, order: ByteOrder, tables: Tables) -> Result<Gost, CryptoError> {
         if key.len() != KEY_SIZE {
-            return Err("(Gost) invalid key size");
+            return Err(CryptoError::InvalidKeyLength { min: KEY_SIZE, max: KEY_SIZE, actual: key.len() });
         }
-
-        let mut k = [0u32; 8];
-        let mut k87 = [0u8; 256];
-        let mut k65 = [0u8; 256];
-        let mut k43 = [0u8; 256];
-        let mut k21 = [0u8; 256];
+        if !sbox.iter().all(is_permutation) {
+            return Err(CryptoError::InvalidParameter("S-box is not a permutation of 0..16"));
+        }
+        let [k1, k2, k3, k4, k5, k6, k7, k8] = sbox;
+
+        let words = match tables {
+            Tables::Bytes => None,
+            Tables::Words => Some(Box::new([[0u32; 256]; 4])),
+        };
+        let mut gt = Gost {
+            k0: 0, k1: 0, k2: 0, k3: 0, k4: 0, k5: 0, k6: 0, k7: 0,
+            k87: [0u8; 256], k65: [0u8; 256], k43: [0u8; 256], k21: [0u8; 256],
+            words, order,
+        };
 
         let mut i = 0usize;
         while i < 256 {
             let idx1 = i >> 4;
             let idx2 = i & 15;
-            k87[i] = (K8[idx1] << 4) | K7[idx2];
-            k65[i] = (K6[idx1] << 4) | K5[idx2];
-            k43[i] = (K4[idx1] << 4) | K3[idx2];
-            k21[i] = (K2[idx1] << 4) | K1[idx2];
+            gt.k87[i] = (k8[idx1] << 4) | k7[idx2];
+            gt.k65[i] = (k6[idx1] << 4) | k5[idx2];
+            gt.k43[i] = (k4[idx1] << 4) | k3[idx2];
+            gt.k21[i] = (k2[idx1] << 4) | k1[idx2];
             i += 1;
         }
 
-        i = 0usize;
-        while i < 8 {
+        let k = [&mut gt.k0, &mut gt.k1, &mut gt.k2, &mut gt.k3, &mut gt.k4, &mut gt.k5, &mut gt.k6, &mut gt.k7];
+        for (i, kx) in k.into_iter().enumerate() {
             let mut idx = (i * 4) + 3;
             let mut v = 0u32;
             v = (v << 8) + (key[idx] as u32);
@@ -68,116 +205,169 @@ impl Gost {
             v = (v << 8) + (key[idx] as u32);
             idx -= 1;
             v = (v << 8) + (key[idx] as u32);
-            k[i] = v;
-            i += 1;
+            *kx = match order {
+                ByteOrder::LittleEndian => v,
+                ByteOrder::BigEndian => v.swap_bytes(),
+            };
         }
 
-        let kptr = &k as *const u32;
-        let k0 = unsafe { *kptr.offset(0) };
-        let k1 = unsafe { *kptr.offset(1) };
-        let k2 = unsafe { *kptr.offset(2) };
-        let k3 = unsafe { *kptr.offset(3) };
-        let k4 = unsafe { *kptr.offset(4) };
-        let k5 = unsafe { *kptr.offset(5) };
-        let k6 = unsafe { *kptr.offset(6) };
-        let k7 = unsafe { *kptr.offset(7) };
+        if let Some(t) = gt.words.as_mut() {
+            for i in 0..256 {
+                t[0][i] = (gt.k87[i] as u32).wrapping_shl(24).rotate_left(11);
+                t[1][i] = (gt.k65[i] as u32).wrapping_shl(16).rotate_left(11);
+                t[2][i] = (gt.k43[i] as u32).wrapping_shl(8).rotate_left(11);
+                t[3][i] = (gt.k21[i] as u32).rotate_left(11);
+            }
+        }
 
-        Ok(Gost { k0, k1, k2, k3, k4, k5, k6, k7, k87, k65, k43, k21 })
+        Ok(gt)
     }
 
     /// Encrypts passed plain text (ECB mode).
-    pub fn encrypt_ecb(&self, input: &[u8]) -> Result<Vec<u8>, &'static str> {
-        if input.is_empty() {
-            return Err("(GOST-ECB) nothing to encrypt");
-        }
+    pub fn encrypt_ecb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb(self, input)
+    }
 
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let nbytes = plain.len();
-        let mut cipher = zeroed_buffer(nbytes);
+    /// Decrypts passsed cipher text (ECB mode).
+    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb(self, cipher)
+    }
 
-        // let mut i = 0usize;
-        // while i < nbytes {
-        for i in (0..nbytes).step_by(BLOCK_SIZE) {
-            let x = self.encrypt_block(bytes2block(&plain[i..]));
-            block2bytes(x, &mut cipher[i..]);
-        }
+    /// Encrypts passed plain-text.
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc(self, input)
+    }
 
-        Ok(cipher)
+    /// Encrypts plain-text with passed IV vector.
+    pub fn encrypt_cbc_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_iv(self, input, iv)
     }
 
-    /// Decrypts passsed cipher text (ECB mode).
-    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Result<Vec<u8>, &'static str> {
-        let nbytes = cipher.len();
-        if nbytes == 0 {
-            return Err("(GOST-ECB) nothing to decrypt");
-        }
+    /// Decrypts passed cipher-text.
+    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc(self, cipher)
+    }
 
-        let mut plain = Vec::with_capacity(nbytes);
-        plain.resize(nbytes, 0);
+    /// Encrypts passed plain text with selected padding (ECB mode).
+    pub fn encrypt_ecb_with(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb_with(self, input, padding)
+    }
 
-        for i in (0..nbytes).step_by(BLOCK_SIZE) {
-            let x = self.decrypt_block(bytes2block(&cipher[i..]));
-            block2bytes(x, &mut plain[i..]);
-        }
+    /// Decrypts passed cipher text and checks selected padding (ECB mode).
+    pub fn decrypt_ecb_with(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb_with(self, cipher, padding)
+    }
 
-        match padding_index(&plain) {
-            Some(idx) => Ok(plain[..idx].to_vec()),
-            _ => Ok(plain)
-        }
+    /// Encrypts passed plain-text with selected padding (CBC mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc_with(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_with(self, input, padding)
     }
 
-    /// Encrypts passed plain-text.
+    /// Encrypts plain-text with passed IV vector and selected padding (CBC mode).
+    pub fn encrypt_cbc_iv_with(&self, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_iv_with(self, input, iv, padding)
+    }
+
+    /// Decrypts passed cipher-text and checks selected padding (CBC mode).
+    pub fn decrypt_cbc_with(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc_with(self, cipher, padding)
+    }
+
+    /// Encrypts passed plain-text with ciphertext stealing (CBC mode).
     /// Before encryption creates IV vector.
-    pub fn encrypt_cbc(&self, input: &[u8]) -> Result<Vec<u8>, &'static str> {
-        self.encrypt_cbc_iv(input, &random_bytes(BLOCK_SIZE))
+    pub fn encrypt_cbc_cts(&self, input: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_cts(self, input, variant)
     }
 
-    /// Encrypts plain-text with passed IV vector.
-    pub fn encrypt_cbc_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, &'static str> {
+    /// Encrypts plain-text with passed IV vector and ciphertext stealing (CBC mode).
+    pub fn encrypt_cbc_cts_iv(&self, input: &[u8], iv: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_cts_iv(self, input, iv, variant)
+    }
+
+    /// Decrypts passed cipher-text with ciphertext stealing (CBC mode).
+    pub fn decrypt_cbc_cts(&self, cipher: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc_cts(self, cipher, variant)
+    }
+
+    /// Encrypts passed plain-text (CTR mode).
+    /// Before encryption creates random nonce.
+    pub fn encrypt_ctr(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ctr(self, input)
+    }
+
+    /// Encrypts plain-text with passed nonce (CTR mode).
+    pub fn encrypt_ctr_nonce(&self, input: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ctr_nonce(self, input, nonce)
+    }
+
+    /// Decrypts passed cipher-text (CTR mode).
+    pub fn decrypt_ctr(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ctr(self, cipher)
+    }
+
+    /// Encrypts/decrypts data in place starting at byte `offset` of the key stream (CTR mode).
+    pub fn apply_ctr(&self, nonce: &[u8], offset: u64, data: &mut [u8]) -> Result<(), CryptoError> {
+        modes::apply_ctr(self, nonce, offset, data)
+    }
+
+    /// Encrypts passed plain-text (GOST gamming mode).
+    /// Before encryption creates synchro-message (IV vector).
+    pub fn encrypt_gamma(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.encrypt_gamma_iv(input, &random_bytes(BLOCK_SIZE))
+    }
+
+    /// Encrypts plain-text with passed synchro-message (GOST gamming mode).
+    /// Synchro-message is saved as the first block of the result, no padding is added.
+    pub fn encrypt_gamma_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
         if iv.len() != BLOCK_SIZE {
-            return Err("(Gost:CBC) invalid size of IV vector");
+            return Err(CryptoError::InvalidIv { expected: BLOCK_SIZE, actual: iv.len() });
         }
         if input.is_empty() {
-            return Err("(Gost:CBC) nothing to encrypt");
+            return Err(CryptoError::EmptyInput);
         }
 
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let nbytes = plain.len();
-        let mut cipher = zeroed_buffer(nbytes + BLOCK_SIZE);
-        cipher[0..BLOCK_SIZE].copy_from_slice(iv);
+        let mut cipher = Vec::with_capacity(input.len() + BLOCK_SIZE);
+        cipher.extend_from_slice(iv);
+        cipher.extend_from_slice(input);
+        self.gamma(iv, &mut cipher[BLOCK_SIZE..]);
+        Ok(cipher)
+    }
 
-        let mut x = bytes2block(iv);
-        for i in (0..nbytes).step_by(BLOCK_SIZE) {
-            let t = bytes2block(&plain[i..]);
-            x = self.encrypt(t.0 ^ x.0, t.1 ^ x.1);
-            block2bytes(x, &mut cipher[(i + BLOCK_SIZE)..]);
+    /// Decrypts passed cipher-text (GOST gamming mode).
+    pub fn decrypt_gamma(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if cipher.len() <= BLOCK_SIZE {
+            return Err(CryptoError::TruncatedCiphertext { block_size: BLOCK_SIZE, actual: cipher.len() });
         }
 
-        Ok(cipher)
+        let mut plain = cipher[BLOCK_SIZE..].to_vec();
+        self.gamma(&cipher[..BLOCK_SIZE], &mut plain);
+        Ok(plain)
     }
 
-    /// Decrypts passed cipher-text.
-    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Result<Vec<u8>, &'static str> {
-        let nbytes = cipher.len();
-        if nbytes <= BLOCK_SIZE {
-            return Err("(Gost::CBC) cipher data size is to short");
-        }
+    /// Encrypts passed plain-text (GOST gamming with feedback).
+    /// Before encryption creates synchro-message (IV vector).
+    pub fn encrypt_gamma_fb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cfb(self, input)
+    }
 
-        let mut plain = zeroed_buffer(nbytes - BLOCK_SIZE);
+    /// Encrypts plain-text with passed synchro-message (GOST gamming with feedback).
+    /// Gamming with feedback is CFB mode with full (64 bit) feedback.
+    pub fn encrypt_gamma_fb_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cfb_iv(self, input, iv)
+    }
 
-        let mut p = bytes2block(&cipher[..]);
-        for i in (BLOCK_SIZE..nbytes).step_by(BLOCK_SIZE) {
-            let x = bytes2block(&cipher[i..]);
-            let t = x;
-            let c = self.decrypt_block(x);
-            words2bytes(c.0 ^ p.0, c.1 ^ p.1, &mut plain[(i - BLOCK_SIZE)..]);
-            p = t;
-        }
+    /// Decrypts passed cipher-text (GOST gamming with feedback).
+    pub fn decrypt_gamma_fb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cfb(self, cipher)
+    }
 
-        match padding_index(&plain) {
-            Some(idx) => Ok(plain[..idx].to_vec()),
-            _ => Ok(plain)
-        }
+    /// Computes imitovstavka (MAC) of passed data with default size (32 bit).
+    pub fn mac(&self, data: &[u8]) -> Vec<u8> {
+        let mut mac = GostMac::new(self);
+        mac.update(data);
+        mac.finalize()
     }
 
     /****************************************************************
@@ -186,9 +376,30 @@ impl Gost {
     *                                                               *
     ****************************************************************/
 
+    /// XORs passed data in place with the gamma (GOST 28147-89, 3.1).
+    /// Encrypted synchro-message is the start value of N3/N4 registers,
+    /// before every block N3 is incremented by C2 (mod 2^32)
+    /// and N4 by C1 (mod 2^32 - 1).
+    fn gamma(&self, iv: &[u8], data: &mut [u8]) {
+        let (mut n3, mut n4) = self.encrypt_tuple(self.load(iv));
+        let mut gamma = [0u8; BLOCK_SIZE];
+
+        for chunk in data.chunks_mut(BLOCK_SIZE) {
+            n3 = n3.wrapping_add(C2);
+            n4 = match n4.overflowing_add(C1) {
+                (v, true) => v.wrapping_add(1),
+                (v, false) => v,
+            };
+            self.store(self.encrypt(n3, n4), &mut gamma);
+            for (d, g) in chunk.iter_mut().zip(gamma.iter()) {
+                *d ^= g;
+            }
+        }
+    }
+
     /// Encrypts plain tuple (2xu32).
     /// Returns encrypted tuple (2xu32).
-    fn encrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
+    fn encrypt_tuple(&self, x: (u32, u32)) -> (u32, u32) {
         self.encrypt(x.0, x.1)
     }
 
@@ -235,7 +446,7 @@ impl Gost {
     }
 
     /// Decrypts cipher tuple (2xu32)
-    fn decrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
+    fn decrypt_tuple(&self, x: (u32, u32)) -> (u32, u32) {
         self.decrypt(x.0, x.1)
     }
 
@@ -281,8 +492,67 @@ impl Gost {
         (xr, xl)
     }
 
+    /// 16 rounds of encryption used by imitovstavka (MAC) mode.
+    /// Returns block without the final swap.
+    fn mac_block(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
+        xr ^= self.f(xl.wrapping_add(self.k0));
+        xl ^= self.f(xr.wrapping_add(self.k1));
+        xr ^= self.f(xl.wrapping_add(self.k2));
+        xl ^= self.f(xr.wrapping_add(self.k3));
+        xr ^= self.f(xl.wrapping_add(self.k4));
+        xl ^= self.f(xr.wrapping_add(self.k5));
+        xr ^= self.f(xl.wrapping_add(self.k6));
+        xl ^= self.f(xr.wrapping_add(self.k7));
+
+        xr ^= self.f(xl.wrapping_add(self.k0));
+        xl ^= self.f(xr.wrapping_add(self.k1));
+        xr ^= self.f(xl.wrapping_add(self.k2));
+        xl ^= self.f(xr.wrapping_add(self.k3));
+        xr ^= self.f(xl.wrapping_add(self.k4));
+        xl ^= self.f(xr.wrapping_add(self.k5));
+        xr ^= self.f(xl.wrapping_add(self.k6));
+        xl ^= self.f(xr.wrapping_add(self.k7));
+
+        (xl, xr)
+    }
+
+    /// Converts bytes to block (N1, N2) in the byte order of the object.
+    fn load(&self, data: &[u8]) -> (u32, u32) {
+        let x = self.order.read_block(data);
+        match self.order {
+            ByteOrder::LittleEndian => x,
+            ByteOrder::BigEndian => (x.1, x.0),
+        }
+    }
+
+    /// Converts block (N1, N2) to bytes in the byte order of the object.
+    fn store(&self, x: (u32, u32), data: &mut [u8]) {
+        match self.order {
+            ByteOrder::LittleEndian => self.order.write_block(x, data),
+            ByteOrder::BigEndian => self.order.write_block((x.1, x.0), data),
+        }
+    }
+
     /// Heart of the algorithm.
+    /// With `constant-time` feature S-boxes are read without secret indexes.
+    #[inline]
     fn f(&self, x: u32) -> u32 {
+        if cfg!(feature = "constant-time") {
+            self.f_constant_time(x)
+        } else {
+            self.f_table(x)
+        }
+    }
+
+    /// Round function with direct table lookups (fast, leaks through cache timing).
+    fn f_table(&self, x: u32) -> u32 {
+        if let Some(t) = &self.words {
+            return t[0][(x >> 24) as u8 as usize]
+                ^ t[1][(x >> 16) as u8 as usize]
+                ^ t[2][(x >> 8) as u8 as usize]
+                ^ t[3][x as u8 as usize];
+        }
+
         let i0 = (x.wrapping_shr(24) & 0xff) as usize;
         let i1 = (x.wrapping_shr(16) & 0xff) as usize;
         let i2 = (x.wrapping_shr(8) & 0xff) as usize;
@@ -300,11 +570,207 @@ impl Gost {
 
         x.wrapping_shl(11) | x.wrapping_shr(32 - 11)
     }
+
+    /// Round function scanning all 16 values of every S-box with masks
+    /// (cache-timing resistant). k21[j] = K2[j >> 4] << 4 | K1[j & 15], so
+    /// K1 is read from k21[0..16] and K2 from every 16th entry.
+    fn f_constant_time(&self, x: u32) -> u32 {
+        let mut y = 0u32;
+        for (i, t) in [&self.k21, &self.k43, &self.k65, &self.k87].into_iter().enumerate() {
+            let b = (x >> (8 * i)) as usize & 0xff;
+            let lo = ct_select((0..16).map(|j| (t[j] & 15) as u32), b & 15);
+            let hi = ct_select((0..16).map(|j| (t[j << 4] >> 4) as u32), b >> 4);
+            y |= (hi << 4 | lo) << (8 * i);
+        }
+        y.rotate_left(11)
+    }
+
+    /// Overwrites key words and tables with zeros (called on drop).
+    fn wipe(&mut self) {
+        for k in [&mut self.k0, &mut self.k1, &mut self.k2, &mut self.k3, &mut self.k4, &mut self.k5, &mut self.k6, &mut self.k7] {
+            zeroize(std::slice::from_mut(k));
+        }
+        zeroize(&mut self.k87);
+        zeroize(&mut self.k65);
+        zeroize(&mut self.k43);
+        zeroize(&mut self.k21);
+        if let Some(t) = self.words.as_mut() {
+            for row in t.iter_mut() {
+                zeroize(row);
+            }
+        }
+    }
+}
+
+impl Drop for Gost {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+impl BlockCipher for Gost {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    type Block = [u8; BLOCK_SIZE];
+
+    fn new(key: &[u8]) -> Result<Gost, CryptoError> {
+        Gost::new(key)
+    }
+
+    fn encrypt_block(&self, block: &mut Self::Block) {
+        let x = self.encrypt_tuple(self.load(block));
+        self.store(x, block);
+    }
+
+    fn decrypt_block(&self, block: &mut Self::Block) {
+        let x = self.decrypt_tuple(self.load(block));
+        self.store(x, block);
+    }
+}
+
+/// Checks if passed S-box contains every value 0..16 exactly once.
+fn is_permutation(k: &[u8; 16]) -> bool {
+    let mut seen = 0u16;
+    for v in k {
+        if *v > 15 {
+            return false;
+        }
+        seen |= 1 << v;
+    }
+    seen == 0xffff
+}
+
+/********************************************************************
+*                                                                   *
+*                   I M I T O V S T A V K A  (MAC)                  *
+*                                                                   *
+********************************************************************/
+
+/// Streaming GOST 28147-89 imitovstavka (MAC) generator.
+pub struct GostMac<'a> {
+    gost: &'a Gost,
+    state: (u32, u32),
+    buffer: [u8; BLOCK_SIZE],
+    filled: usize,
+    nblocks: usize,
+    tag_size: usize,
+}
+
+impl<'a> GostMac<'a> {
+    /// Creates MAC generator producing 32 bit tags.
+    pub fn new(gost: &'a Gost) -> GostMac<'a> {
+        GostMac { gost, state: (0, 0), buffer: [0u8; BLOCK_SIZE], filled: 0, nblocks: 0, tag_size: MAC_SIZE }
+    }
+
+    /// Creates MAC generator producing tags of passed size (1..=8 bytes).
+    pub fn with_tag_size(gost: &'a Gost, tag_size: usize) -> Result<GostMac<'a>, CryptoError> {
+        if !(1..=BLOCK_SIZE).contains(&tag_size) {
+            return Err(CryptoError::InvalidParameter("MAC tag size must be 1..=8 bytes"));
+        }
+        let mut mac = GostMac::new(gost);
+        mac.tag_size = tag_size;
+        Ok(mac)
+    }
+
+    /// Adds passed data to the MAC.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.filled == BLOCK_SIZE {
+                self.process_buffer();
+            }
+            let n = (BLOCK_SIZE - self.filled).min(data.len());
+            self.buffer[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            data = &data[n..];
+        }
+    }
+
+    /// Returns the tag.
+    /// Last incomplete block is padded with zeros. A message shorter than
+    /// two blocks is extended with zero block (as required by the standard).
+    pub fn finalize(mut self) -> Vec<u8> {
+        if self.filled > 0 || self.nblocks == 0 {
+            self.buffer[self.filled..].fill(0);
+            self.filled = BLOCK_SIZE;
+            self.process_buffer();
+        }
+        if self.nblocks == 1 {
+            self.filled = BLOCK_SIZE;
+            self.process_buffer();
+        }
+
+        let mut tag = [0u8; BLOCK_SIZE];
+        self.gost.store(self.state, &mut tag);
+        tag[..self.tag_size].to_vec()
+    }
+
+    /// Checks (in constant time) if passed tag matches the data.
+    pub fn verify(self, tag: &[u8]) -> bool {
+        constant_time_eq(&self.finalize(), tag)
+    }
+
+    fn process_buffer(&mut self) {
+        let x = self.gost.load(&self.buffer);
+        self.state = self.gost.mac_block(self.state.0 ^ x.0, self.state.1 ^ x.1);
+        self.buffer = [0u8; BLOCK_SIZE];
+        self.filled = 0;
+        self.nblocks += 1;
+    }
+}
+
+impl Drop for GostMac<'_> {
+    fn drop(&mut self) {
+        zeroize(std::slice::from_mut(&mut self.state));
+        zeroize(&mut self.buffer);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::vectors::{self, hex, parse};
+    use crate::crypto::tests::sample_words;
+
+    fn sbox(name: &str) -> &'static SBox {
+        match name {
+            "default" => &SBOX_DEFAULT,
+            "test" => &SBOX_TEST_PARAMSET,
+            "cryptopro-a" => &SBOX_CRYPTOPRO_A,
+            "cryptopro-b" => &SBOX_CRYPTOPRO_B,
+            "cryptopro-c" => &SBOX_CRYPTOPRO_C,
+            "cryptopro-d" => &SBOX_CRYPTOPRO_D,
+            "tc26-z" => &SBOX_TC26_Z,
+            _ => panic!("unknown S-box {}", name),
+        }
+    }
+
+    #[test]
+    fn test_ecb_vectors() {
+        for v in parse(include_str!("testdata/gost_ecb.txt")) {
+            let gt = Gost::with_sbox(&hex(v[1]), sbox(v[0])).unwrap();
+            let (plain, cipher) = (hex(v[2]), hex(v[3]));
+            assert_eq!(gt.encrypt_ecb_with(&plain, Padding::None).unwrap(), cipher, "key {}", v[1]);
+            assert_eq!(gt.decrypt_ecb_with(&cipher, Padding::None).unwrap(), plain, "key {}", v[1]);
+        }
+    }
+
+    #[test]
+    fn test_gamma_vectors() {
+        for v in parse(include_str!("testdata/gost_gamma.txt")) {
+            let gt = Gost::with_sbox(&hex(v[1]), sbox(v[0])).unwrap();
+            let (iv, plain, cipher) = (hex(v[2]), hex(v[3]), hex(v[4]));
+            let encrypted = gt.encrypt_gamma_iv(&plain, &iv).unwrap();
+            assert_eq!(encrypted[BLOCK_SIZE..], cipher[..], "S-box {}, {} bytes", v[0], plain.len());
+            assert_eq!(gt.decrypt_gamma(&encrypted).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn test_mac_vectors() {
+        for v in parse(include_str!("testdata/gost_mac.txt")) {
+            let gt = Gost::with_sbox(&hex(v[1]), sbox(v[0])).unwrap();
+            assert_eq!(gt.mac(&hex(v[2])), hex(v[3]), "S-box {}, {} bytes", v[0], v[2].len() / 2);
+        }
+    }
 
     #[test]
     fn test_block() {
@@ -327,9 +793,9 @@ mod tests {
         ];
         let mut i = 0usize;
         while i < plain.len() {
-            let encrypted = gt.encrypt_block(plain[i]);
+            let encrypted = gt.encrypt_tuple(plain[i]);
             assert_eq!(expected[i], encrypted);
-            let decrypted = gt.decrypt_block(encrypted);
+            let decrypted = gt.decrypt_tuple(encrypted);
             assert_eq!(plain[i], decrypted);
             i += 1;
         }
@@ -367,8 +833,145 @@ mod tests {
             0xc6, 0x9a, 0x53, 0x4f, 0xce];
         let iv = [0xf8u8, 0xa4, 0x9e, 0x45, 0x40, 0xa5, 0x65, 0xc8];
 
-        let cipher = gt.encrypt_cbc_iv(&plain, &iv);
+        let cipher = gt.encrypt_cbc_iv(plain, &iv);
         assert!(cipher.is_ok());
         assert_eq!(cipher.unwrap(), expt);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gamma() {
+        let key = [0u8, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0, 6, 0, 0, 0, 7, 0, 0, 0];
+        let gt = Gost::new(&key).unwrap();
+        let iv = [0xf8u8, 0xa4, 0x9e, 0x45, 0x40, 0xa5, 0x65, 0xc8];
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+
+        let cipher = gt.encrypt_gamma_iv(plain, &iv).unwrap();
+        assert_eq!(cipher.len(), plain.len() + BLOCK_SIZE);
+        assert_eq!(gt.decrypt_gamma(&cipher).unwrap(), plain);
+
+        // first gamma block: E(E(IV) + (C2, C1))
+        let s = gt.encrypt_tuple(bytes2block(&iv));
+        let mut gamma = [0u8; BLOCK_SIZE];
+        block2bytes(gt.encrypt(s.0.wrapping_add(C2), s.1.wrapping_add(C1)), &mut gamma);
+        for i in 0..BLOCK_SIZE {
+            assert_eq!(cipher[BLOCK_SIZE + i], plain[i] ^ gamma[i]);
+        }
+
+        let cipher = gt.encrypt_gamma_fb_iv(plain, &iv).unwrap();
+        assert_eq!(gt.decrypt_gamma_fb(&cipher).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_mac() {
+        let key = [0u8, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0, 6, 0, 0, 0, 7, 0, 0, 0];
+        let gt = Gost::new(&key).unwrap();
+        let data = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+
+        let tag = gt.mac(data);
+        assert_eq!(tag.len(), MAC_SIZE);
+
+        // streaming in pieces gives the same tag
+        let mut mac = GostMac::new(&gt);
+        for chunk in data.chunks(3) {
+            mac.update(chunk);
+        }
+        assert!(mac.verify(&tag));
+
+        // corrupted data is detected
+        let mut corrupted = data.to_vec();
+        corrupted[10] ^= 1;
+        let mut mac = GostMac::new(&gt);
+        mac.update(&corrupted);
+        assert!(!mac.verify(&tag));
+
+        // longer tag starts with the default one
+        let mut mac = GostMac::with_tag_size(&gt, 8).unwrap();
+        mac.update(data);
+        assert_eq!(mac.finalize()[..MAC_SIZE], tag[..]);
+        assert!(GostMac::with_tag_size(&gt, 9).is_err());
+    }
+
+    #[test]
+    fn test_sbox() {
+        for sbox in [SBOX_DEFAULT, SBOX_TEST_PARAMSET, SBOX_CRYPTOPRO_A, SBOX_CRYPTOPRO_B, SBOX_CRYPTOPRO_C, SBOX_CRYPTOPRO_D, SBOX_TC26_Z] {
+            assert!(sbox.iter().all(is_permutation));
+        }
+
+        let key = [0u8, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0, 6, 0, 0, 0, 7, 0, 0, 0];
+        let default = Gost::with_sbox(&key, &SBOX_DEFAULT).unwrap();
+        assert_eq!(default.encrypt(0, 0), (0x37ef7123u32, 0x361b7184u32));
+        let other = Gost::with_sbox(&key, &SBOX_CRYPTOPRO_A).unwrap();
+        assert_ne!(other.encrypt(0, 0), default.encrypt(0, 0));
+
+        let mut invalid = SBOX_DEFAULT;
+        invalid[3][0] = invalid[3][1];
+        assert!(Gost::with_sbox(&key, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_magma_block() {
+        // GOST R 34.12-2015, A.2 (key words K1..K8 are big-endian)
+        let key = [0xccu8, 0xdd, 0xee, 0xff, 0x88, 0x99, 0xaa, 0xbb, 0x44, 0x55, 0x66, 0x77, 0x00, 0x11, 0x22, 0x33,
+            0xf3, 0xf2, 0xf1, 0xf0, 0xf7, 0xf6, 0xf5, 0xf4, 0xfb, 0xfa, 0xf9, 0xf8, 0xff, 0xfe, 0xfd, 0xfc];
+        let gt = Gost::with_sbox(&key, &SBOX_TC26_Z).unwrap();
+        assert_eq!(gt.encrypt(0x76543210, 0xfedcba98), (0xc2d8ca3d, 0x4ee901e5));
+        assert_eq!(gt.decrypt(0xc2d8ca3d, 0x4ee901e5), (0x76543210, 0xfedcba98));
+    }
+
+    #[test]
+    fn test_byte_order() {
+        // GOST R 34.13-2015, A.2.1 (key and blocks as in the standard)
+        let key = vectors::hex("ffeeddccbbaa99887766554433221100f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let gt = Gost::with_sbox_and_order(&key, &SBOX_TC26_Z, ByteOrder::BigEndian).unwrap();
+        let plain = vectors::hex("92def06b3c130a59db54c704f8189d204a98fb2e67a8024c8912409b17b57e41");
+        let cipher = vectors::hex("2b073f0494f372a0de70e715d3556e4811d8d9e9eacfbc1e7c68260996c67efb");
+        assert_eq!(gt.encrypt_ecb_with(&plain, Padding::None).unwrap(), cipher);
+        assert_eq!(gt.decrypt_ecb_with(&cipher, Padding::None).unwrap(), plain);
+
+        // the same words in little-endian order
+        let swapped = |data: &[u8]| data.chunks(4).flat_map(|w| w.iter().rev().copied()).collect::<Vec<u8>>();
+        let gt = Gost::with_sbox(&swapped(&key), &SBOX_TC26_Z).unwrap();
+        let block = |data: &[u8]| data.chunks(8).flat_map(|b| b.iter().rev().copied()).collect::<Vec<u8>>();
+        assert_eq!(gt.encrypt_ecb_with(&block(&plain), Padding::None).unwrap(), block(&cipher));
+    }
+
+    #[test]
+    fn test_tables() {
+        let key = b"12345678901234567890123456789012";
+        for sbox in [&SBOX_DEFAULT, &SBOX_TEST_PARAMSET, &SBOX_TC26_Z] {
+            let fast = Gost::with_tables(key, sbox, ByteOrder::LittleEndian, Tables::Words).unwrap();
+            let small = Gost::with_tables(key, sbox, ByteOrder::LittleEndian, Tables::Bytes).unwrap();
+            assert!(fast.words.is_some() && small.words.is_none());
+            for x in [0u32, 1, 0x12345678, 0x80000000, 0xdeadbeef, 0xffffffff] {
+                assert_eq!(fast.f(x), small.f(x));
+            }
+            let plain: Vec<u8> = (0..64u8).collect();
+            assert_eq!(fast.encrypt_ecb(&plain).unwrap(), small.encrypt_ecb(&plain).unwrap());
+            assert_eq!(fast.mac(&plain), small.mac(&plain));
+        }
+    }
+
+    /// Round function reading whole 8 bit tables agrees with the word table
+    /// lookups for every preset S-box (the vectors cover only some of them).
+    #[test]
+    fn test_constant_time() {
+        let key = b"12345678901234567890123456789012";
+        for sbox in [&SBOX_DEFAULT, &SBOX_CRYPTOPRO_A, &SBOX_CRYPTOPRO_B, &SBOX_CRYPTOPRO_C, &SBOX_CRYPTOPRO_D, &SBOX_TC26_Z] {
+            let gt = Gost::with_sbox(key, sbox).unwrap();
+            for x in sample_words() {
+                assert_eq!(gt.f_constant_time(x), gt.f_table(x), "x = {:#010x}", x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wipe() {
+        let key = b"12345678901234567890123456789012";
+        let mut gt = Gost::new(key).unwrap();
+        assert_ne!(gt.k0, 0);
+        gt.wipe();
+        assert_eq!([gt.k0, gt.k1, gt.k2, gt.k3, gt.k4, gt.k5, gt.k6, gt.k7], [0u32; 8]);
+        assert!(gt.k87.iter().chain(&gt.k65).chain(&gt.k43).chain(&gt.k21).all(|&x| x == 0));
+        assert!(gt.words.as_ref().unwrap().iter().flatten().all(|&x| x == 0));
+    }
+}
@@ -0,0 +1,235 @@
+//! Password-based key derivation.
+//! PBKDF2-HMAC-SHA256 (RFC 8018) and memory-hard scrypt (RFC 7914).
+
+use crate::crypto::CryptoError;
+use crate::crypto::sha256::{HmacSha256, DIGEST_SIZE};
+
+/// Parameters of the key derivation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KdfParams {
+    /// PBKDF2-HMAC-SHA256 with passed number of iterations.
+    Pbkdf2 { iterations: u32 },
+    /// scrypt with cost N = 2^log_n, block size r and parallelization p.
+    Scrypt { log_n: u8, r: u32, p: u32 },
+}
+
+// limits of parameters accepted by `derive_key` (parameters may come from untrusted data)
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+pub const MAX_SCRYPT_LOG_N: u8 = 20;
+// scrypt memory 128 * r * 2^log_n bytes (1 GiB)
+pub const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+// scrypt work p * r * 2^log_n (BlockMix calls of 128 bytes)
+pub const MAX_SCRYPT_WORK: u64 = 1 << 26;
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::Scrypt { log_n: 15, r: 8, p: 1 }
+    }
+}
+
+impl KdfParams {
+    /// Checks if parameters are within limits, so derivation
+    /// takes bounded time and memory (up to 1 GiB for scrypt).
+    pub fn validate(&self) -> Result<(), CryptoError> {
+        match *self {
+            KdfParams::Pbkdf2 { iterations } if iterations == 0 || iterations > MAX_PBKDF2_ITERATIONS => {
+                Err(CryptoError::InvalidParameter("PBKDF2 number of iterations is out of range"))
+            }
+            KdfParams::Scrypt { log_n, r, p } if log_n == 0 || log_n > MAX_SCRYPT_LOG_N || r == 0 || p == 0 => {
+                Err(CryptoError::InvalidParameter("scrypt parameters are out of range"))
+            }
+            KdfParams::Scrypt { log_n, r, .. } if 128 * ((r as u64) << log_n) > MAX_SCRYPT_MEMORY => {
+                Err(CryptoError::InvalidParameter("scrypt needs too much memory"))
+            }
+            KdfParams::Scrypt { log_n, r, p } if p as u64 * ((r as u64) << log_n) > MAX_SCRYPT_WORK => {
+                Err(CryptoError::InvalidParameter("scrypt needs too much work"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Derives key of passed size (out.len()) from password and salt.
+/// Parameters out of limits (see `KdfParams::validate`) are rejected.
+pub fn derive_key(password: &[u8], salt: &[u8], params: &KdfParams, out: &mut [u8]) -> Result<(), CryptoError> {
+    if out.is_empty() {
+        return Err(CryptoError::InvalidParameter("derived key size must be positive"));
+    }
+    params.validate()?;
+    match *params {
+        KdfParams::Pbkdf2 { iterations } => pbkdf2_hmac_sha256(password, salt, iterations, out),
+        KdfParams::Scrypt { log_n, r, p } => scrypt(password, salt, log_n, r, p, out),
+    }
+}
+
+/// PBKDF2 with HMAC-SHA256 as pseudo-random function.
+pub fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) -> Result<(), CryptoError> {
+    if iterations == 0 {
+        return Err(CryptoError::InvalidParameter("PBKDF2 number of iterations must be positive"));
+    }
+
+    let prf = HmacSha256::new(password);
+    for (i, chunk) in out.chunks_mut(DIGEST_SIZE).enumerate() {
+        let mut hmac = prf.clone();
+        hmac.update(salt);
+        hmac.update(&(i as u32 + 1).to_be_bytes());
+        let mut u = hmac.finalize();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut hmac = prf.clone();
+            hmac.update(&u);
+            u = hmac.finalize();
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+    Ok(())
+}
+
+/// scrypt key derivation (memory usage is 128 * r * 2^log_n bytes).
+pub fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, out: &mut [u8]) -> Result<(), CryptoError> {
+    if log_n == 0 || log_n >= 32 || r == 0 || p == 0 {
+        return Err(CryptoError::InvalidParameter("scrypt parameters"));
+    }
+    if (r as u64) * (p as u64) >= 1 << 30 {
+        return Err(CryptoError::InvalidParameter("scrypt parameters"));
+    }
+
+    // sizes of buffers must not overflow
+    let n = 1usize.checked_shl(log_n as u32).ok_or(CryptoError::InvalidParameter("scrypt parameters"))?;
+    let block_size = (r as usize).checked_mul(128).ok_or(CryptoError::InvalidParameter("scrypt parameters"))?;
+    let nbytes = block_size.checked_mul(p as usize).ok_or(CryptoError::InvalidParameter("scrypt parameters"))?;
+    let vbytes = block_size.checked_mul(n).ok_or(CryptoError::InvalidParameter("scrypt parameters"))?;
+    let mut b = vec![0u8; nbytes];
+    pbkdf2_hmac_sha256(password, salt, 1, &mut b)?;
+
+    let mut v = vec![0u32; vbytes / 4];
+    for chunk in b.chunks_mut(block_size) {
+        let mut x: Vec<u32> = chunk.chunks(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect();
+        ro_mix(&mut x, &mut v, n);
+        for (w, bytes) in x.iter().zip(chunk.chunks_mut(4)) {
+            bytes.copy_from_slice(&w.to_le_bytes());
+        }
+    }
+
+    pbkdf2_hmac_sha256(password, &b, 1, out)
+}
+
+/// scryptROMix (RFC 7914, 5).
+fn ro_mix(x: &mut [u32], v: &mut [u32], n: usize) {
+    let len = x.len();
+    let mut y = vec![0u32; len];
+
+    for i in 0..n {
+        v[i * len..(i + 1) * len].copy_from_slice(x);
+        block_mix(x, &mut y);
+    }
+    for _ in 0..n {
+        // integerify: first word of the last 64 byte block
+        let j = (x[len - 16] as usize) & (n - 1);
+        for (x, v) in x.iter_mut().zip(&v[j * len..(j + 1) * len]) {
+            *x ^= v;
+        }
+        block_mix(x, &mut y);
+    }
+}
+
+/// scryptBlockMix (RFC 7914, 4), `y` is a scratch buffer.
+fn block_mix(b: &mut [u32], y: &mut [u32]) {
+    let r2 = b.len() / 16;
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[(r2 - 1) * 16..]);
+
+    for i in 0..r2 {
+        for (x, b) in x.iter_mut().zip(&b[i * 16..(i + 1) * 16]) {
+            *x ^= b;
+        }
+        salsa20_8(&mut x);
+        // even blocks go to the first half, odd to the second one
+        let dst = (i / 2 + (i % 2) * (r2 / 2)) * 16;
+        y[dst..dst + 16].copy_from_slice(&x);
+    }
+    b.copy_from_slice(y);
+}
+
+/// Salsa20/8 core (RFC 7914, 3).
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    for _ in 0..4 {
+        // columns
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 5, 9, 13, 1);
+        quarter_round(&mut x, 10, 14, 2, 6);
+        quarter_round(&mut x, 15, 3, 7, 11);
+        // rows
+        quarter_round(&mut x, 0, 1, 2, 3);
+        quarter_round(&mut x, 5, 6, 7, 4);
+        quarter_round(&mut x, 10, 11, 8, 9);
+        quarter_round(&mut x, 15, 12, 13, 14);
+    }
+    for (b, x) in b.iter_mut().zip(x.iter()) {
+        *b = b.wrapping_add(*x);
+    }
+}
+
+/// Salsa20 quarter-round on words y0, y1, y2, y3.
+fn quarter_round(x: &mut [u32; 16], y0: usize, y1: usize, y2: usize, y3: usize) {
+    x[y1] ^= x[y0].wrapping_add(x[y3]).rotate_left(7);
+    x[y2] ^= x[y1].wrapping_add(x[y0]).rotate_left(9);
+    x[y3] ^= x[y2].wrapping_add(x[y1]).rotate_left(13);
+    x[y0] ^= x[y3].wrapping_add(x[y2]).rotate_left(18);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_pbkdf2() {
+        // RFC 7914, 11
+        let mut out = [0u8; 64];
+        pbkdf2_hmac_sha256(b"passwd", b"salt", 1, &mut out).unwrap();
+        assert_eq!(hex(&out), "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                               49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783");
+        assert!(pbkdf2_hmac_sha256(b"passwd", b"salt", 0, &mut out).is_err());
+    }
+
+    #[test]
+    fn test_scrypt() {
+        // RFC 7914, 12
+        let mut out = [0u8; 64];
+        scrypt(b"", b"", 4, 1, 1, &mut out).unwrap();
+        assert_eq!(hex(&out), "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+                               fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906");
+        scrypt(b"password", b"NaCl", 10, 8, 16, &mut out).unwrap();
+        assert_eq!(hex(&out), "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162\
+                               2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640");
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut out = [0u8; 32];
+        for params in [
+            KdfParams::Pbkdf2 { iterations: 0 },
+            KdfParams::Pbkdf2 { iterations: u32::MAX },
+            KdfParams::Scrypt { log_n: 31, r: 8, p: 1 },
+            KdfParams::Scrypt { log_n: 21, r: 1, p: 1 },
+            KdfParams::Scrypt { log_n: 10, r: 1 << 20, p: 1 << 10 },
+            KdfParams::Scrypt { log_n: 10, r: 0, p: 1 },
+            KdfParams::Scrypt { log_n: 20, r: 1024, p: 1 },
+            KdfParams::Scrypt { log_n: 20, r: 8, p: 128 },
+        ] {
+            assert!(matches!(params.validate(), Err(CryptoError::InvalidParameter(_))), "{:?}", params);
+            assert!(derive_key(b"passwd", b"salt", &params, &mut out).is_err(), "{:?}", params);
+        }
+        assert!(KdfParams::default().validate().is_ok());
+        assert!(KdfParams::Scrypt { log_n: MAX_SCRYPT_LOG_N, r: 8, p: 1 }.validate().is_ok());
+        assert!(KdfParams::Scrypt { log_n: 10, r: 8, p: 16 }.validate().is_ok());
+    }
+}
@@ -0,0 +1,326 @@
+//! GOST R 34.12-2015 "Magma" with modes of GOST R 34.13-2015 (RFC 8891).
+//!
+//! Magma is GOST 28147-89 with id-tc26-gost-28147-param-Z S-box and
+//! big-endian blocks and keys (the first byte is the most significant one).
+//! Modes follow the standard: IV vectors are not stored in the output,
+//! CBC, CFB and OFB use shift registers of `m` bytes (multiple of the block
+//! size for CBC and OFB) and `s` is the size of a segment in bytes.
+
+use crate::crypto::*;
+use crate::crypto::cmac::Cmac;
+use crate::crypto::gost::{Gost, SBOX_TC26_Z};
+
+const BLOCK_SIZE: usize = 8;
+
+pub struct Magma {
+    gost: Gost,
+}
+
+impl Magma {
+    /// Creates magma-object for passed key (32 bytes).
+    pub fn new(key: &[u8]) -> Result<Magma, CryptoError> {
+        let gost = Gost::with_sbox_and_order(key, &SBOX_TC26_Z, ByteOrder::BigEndian)?;
+        Ok(Magma { gost })
+    }
+
+    /// Encrypts passed plain text (ECB mode).
+    pub fn encrypt_ecb(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb_with(self, input, padding)
+    }
+
+    /// Decrypts passed cipher text (ECB mode).
+    pub fn decrypt_ecb(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb_with(self, cipher, padding)
+    }
+
+    /// Encrypts passed plain text (CTR mode), IV vector has half of the block.
+    pub fn encrypt_ctr(&self, input: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        ctr(self, input, iv, s)
+    }
+
+    /// Decrypts passed cipher text (CTR mode).
+    pub fn decrypt_ctr(&self, cipher: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        ctr(self, cipher, iv, s)
+    }
+
+    /// Encrypts passed plain text (OFB mode).
+    pub fn encrypt_ofb(&self, input: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        ofb(self, input, iv, s)
+    }
+
+    /// Decrypts passed cipher text (OFB mode).
+    pub fn decrypt_ofb(&self, cipher: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        ofb(self, cipher, iv, s)
+    }
+
+    /// Encrypts passed plain text (CBC mode).
+    pub fn encrypt_cbc(&self, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        check_register::<Self>(iv, true)?;
+        let mut data = padding.pad(input, BLOCK_SIZE)?;
+        if data.is_empty() {
+            return Err(CryptoError::EmptyInput);
+        }
+
+        let mut r = iv.to_vec();
+        for chunk in data.chunks_mut(BLOCK_SIZE) {
+            let mut block = load::<Self>(chunk);
+            xor_into(&mut block, &r[..BLOCK_SIZE]);
+            self.encrypt_block(&mut block);
+            chunk.copy_from_slice(&block);
+            shift(&mut r, chunk);
+        }
+        Ok(data)
+    }
+
+    /// Decrypts passed cipher text (CBC mode).
+    pub fn decrypt_cbc(&self, cipher: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        check_register::<Self>(iv, true)?;
+        if cipher.is_empty() || !cipher.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(CryptoError::TruncatedCiphertext { block_size: BLOCK_SIZE, actual: cipher.len() });
+        }
+
+        let mut data = Vec::with_capacity(cipher.len());
+        let mut r = iv.to_vec();
+        for chunk in cipher.chunks(BLOCK_SIZE) {
+            let mut block = load::<Self>(chunk);
+            self.decrypt_block(&mut block);
+            xor_into(&mut block, &r[..BLOCK_SIZE]);
+            data.extend_from_slice(&block);
+            shift(&mut r, chunk);
+        }
+        Ok(padding.unpad(&data, BLOCK_SIZE)?.to_vec())
+    }
+
+    /// Encrypts passed plain text (CFB mode).
+    pub fn encrypt_cfb(&self, input: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        cfb(self, input, iv, s, false)
+    }
+
+    /// Decrypts passed cipher text (CFB mode).
+    pub fn decrypt_cfb(&self, cipher: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        cfb(self, cipher, iv, s, true)
+    }
+
+    /// Computes MAC of passed data truncated to `s` bytes.
+    pub fn mac(&self, data: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+        check_segment::<Self>(s)?;
+        let mut tag = cmac::mac(self, data)?;
+        tag.truncate(s);
+        Ok(tag)
+    }
+
+    /// Checks (in constant time) if passed MAC (at least 4 bytes) matches the data.
+    pub fn verify_mac(&self, data: &[u8], tag: &[u8]) -> bool {
+        match Cmac::new(self) {
+            Ok(mut cmac) => {
+                cmac.update(data);
+                cmac.verify(tag)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl BlockCipher for Magma {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    type Block = [u8; BLOCK_SIZE];
+
+    fn new(key: &[u8]) -> Result<Magma, CryptoError> {
+        Magma::new(key)
+    }
+
+    fn encrypt_block(&self, block: &mut Self::Block) {
+        self.gost.encrypt_block(block);
+    }
+
+    fn decrypt_block(&self, block: &mut Self::Block) {
+        self.gost.decrypt_block(block);
+    }
+}
+
+/********************************************************************
+*                                                                   *
+*                  G O S T   R   3 4 . 1 3   M O D E S              *
+*                                                                   *
+********************************************************************/
+
+/// CTR mode: counter is IV || 0, every counter gives `s` bytes of gamma.
+fn ctr<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+    if iv.len() != C::BLOCK_SIZE / 2 {
+        return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE / 2, actual: iv.len() });
+    }
+    check_segment::<C>(s)?;
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+
+    let mut nonce = C::Block::default();
+    nonce.as_mut()[..iv.len()].copy_from_slice(iv);
+    let mut data = input.to_vec();
+    for (index, chunk) in data.chunks_mut(s).enumerate() {
+        let mut gamma = counter_block::<C>(nonce.as_ref(), index as u64);
+        cipher.encrypt_block(&mut gamma);
+        xor_into(chunk, gamma.as_ref());
+    }
+    Ok(data)
+}
+
+/// OFB mode: encrypted block goes to the register, `s` bytes of it are gamma.
+fn ofb<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], s: usize) -> Result<Vec<u8>, CryptoError> {
+    check_register::<C>(iv, true)?;
+    check_segment::<C>(s)?;
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+
+    let mut data = input.to_vec();
+    let mut r = iv.to_vec();
+    for chunk in data.chunks_mut(s) {
+        let mut gamma = load::<C>(&r);
+        cipher.encrypt_block(&mut gamma);
+        xor_into(chunk, gamma.as_ref());
+        shift(&mut r, gamma.as_ref());
+    }
+    Ok(data)
+}
+
+/// CFB mode: `s` bytes of cipher text go to the register.
+fn cfb<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], s: usize, decrypt: bool) -> Result<Vec<u8>, CryptoError> {
+    check_register::<C>(iv, false)?;
+    check_segment::<C>(s)?;
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+
+    let mut data = input.to_vec();
+    let mut r = iv.to_vec();
+    for chunk in data.chunks_mut(s) {
+        let mut gamma = load::<C>(&r);
+        cipher.encrypt_block(&mut gamma);
+        if decrypt {
+            shift(&mut r, chunk);
+            xor_into(chunk, gamma.as_ref());
+        } else {
+            xor_into(chunk, gamma.as_ref());
+            shift(&mut r, chunk);
+        }
+    }
+    Ok(data)
+}
+
+/// Shifts register left by length of passed bytes and puts them at the end.
+/// The last (shorter) segment of data does not need correct register.
+fn shift(r: &mut [u8], bytes: &[u8]) {
+    let n = bytes.len().min(r.len());
+    r.rotate_left(n);
+    let len = r.len();
+    r[len - n..].copy_from_slice(&bytes[..n]);
+}
+
+/// Checks size of register: at least one block (multiple of blocks if needed).
+fn check_register<C: BlockCipher>(iv: &[u8], blocks: bool) -> Result<(), CryptoError> {
+    if iv.len() < C::BLOCK_SIZE || (blocks && !iv.len().is_multiple_of(C::BLOCK_SIZE)) {
+        return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE, actual: iv.len() });
+    }
+    Ok(())
+}
+
+/// Checks size of segment (1 - block size bytes).
+fn check_segment<C: BlockCipher>(s: usize) -> Result<(), CryptoError> {
+    if s == 0 || s > C::BLOCK_SIZE {
+        return Err(CryptoError::InvalidParameter("segment size must be between 1 and block size"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::vectors::hex;
+
+    // GOST R 34.13-2015, A.2
+    const KEY: &str = "ffeeddccbbaa99887766554433221100f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff";
+    const PLAIN: &str = "92def06b3c130a59db54c704f8189d204a98fb2e67a8024c8912409b17b57e41";
+    const IV: &str = "1234567890abcdef234567890abcdef1";
+
+    #[test]
+    fn test_ecb() {
+        let magma = Magma::new(&hex(KEY)).unwrap();
+        let plain = hex(PLAIN);
+        let cipher = hex("2b073f0494f372a0de70e715d3556e4811d8d9e9eacfbc1e7c68260996c67efb");
+        assert_eq!(magma.encrypt_ecb(&plain, Padding::None).unwrap(), cipher);
+        assert_eq!(magma.decrypt_ecb(&cipher, Padding::None).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_ctr() {
+        let magma = Magma::new(&hex(KEY)).unwrap();
+        let plain = hex(PLAIN);
+        let iv = hex("12345678");
+        let cipher = hex("4e98110c97b7b93c3e250d93d6e85d69136d868807b2dbef568eb680ab52a12d");
+        assert_eq!(magma.encrypt_ctr(&plain, &iv, 8).unwrap(), cipher);
+        assert_eq!(magma.decrypt_ctr(&cipher, &iv, 8).unwrap(), plain);
+
+        // with full segments it is CTR mode of `modes`
+        let mut data = plain.clone();
+        modes::apply_ctr(&magma, &hex("1234567800000000"), 0, &mut data).unwrap();
+        assert_eq!(data, cipher);
+
+        // shorter segment uses the first bytes of gamma
+        let encrypted = magma.encrypt_ctr(&plain, &iv, 4).unwrap();
+        assert_eq!(encrypted[..4], cipher[..4]);
+        assert_eq!(magma.decrypt_ctr(&encrypted, &iv, 4).unwrap(), plain);
+        assert!(magma.encrypt_ctr(&plain, &hex(IV), 8).is_err());
+    }
+
+    #[test]
+    fn test_ofb() {
+        let magma = Magma::new(&hex(KEY)).unwrap();
+        let plain = hex(PLAIN);
+        let cipher = hex("db37e0e266903c830d46644c1f9a089ca0f83062430e327ec824efb8bd4fdb05");
+        assert_eq!(magma.encrypt_ofb(&plain, &hex(IV), 8).unwrap(), cipher);
+        assert_eq!(magma.decrypt_ofb(&cipher, &hex(IV), 8).unwrap(), plain);
+        assert!(magma.encrypt_ofb(&plain, &hex(IV)[..12], 8).is_err());
+    }
+
+    #[test]
+    fn test_cbc() {
+        let magma = Magma::new(&hex(KEY)).unwrap();
+        let plain = hex(PLAIN);
+        // register of three blocks
+        let iv = hex("1234567890abcdef234567890abcdef134567890abcdef12");
+        let cipher = hex("96d1b05eea683919aff76129abb937b95058b4a1c4bc001920b78b1a7cd7e667");
+        assert_eq!(magma.encrypt_cbc(&plain, &iv, Padding::None).unwrap(), cipher);
+        assert_eq!(magma.decrypt_cbc(&cipher, &iv, Padding::None).unwrap(), plain);
+
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+        let encrypted = magma.encrypt_cbc(plain, &hex(IV), Padding::Iso7816).unwrap();
+        assert_eq!(magma.decrypt_cbc(&encrypted, &hex(IV), Padding::Iso7816).unwrap(), plain);
+        assert!(magma.decrypt_cbc(&encrypted[1..], &hex(IV), Padding::Iso7816).is_err());
+    }
+
+    #[test]
+    fn test_cfb() {
+        let magma = Magma::new(&hex(KEY)).unwrap();
+        let plain = hex(PLAIN);
+        let cipher = hex("db37e0e266903c830d46644c1f9a089c24bdd2035315d38bbcc0321421075505");
+        assert_eq!(magma.encrypt_cfb(&plain, &hex(IV), 8).unwrap(), cipher);
+        assert_eq!(magma.decrypt_cfb(&cipher, &hex(IV), 8).unwrap(), plain);
+
+        for s in 1..=8 {
+            let encrypted = magma.encrypt_cfb(&plain[..29], &hex(IV), s).unwrap();
+            assert_eq!(encrypted[..s], cipher[..s]);
+            assert_eq!(magma.decrypt_cfb(&encrypted, &hex(IV), s).unwrap(), plain[..29]);
+        }
+        assert!(magma.encrypt_cfb(&plain, &hex(IV), 9).is_err());
+    }
+
+    #[test]
+    fn test_mac() {
+        let magma = Magma::new(&hex(KEY)).unwrap();
+        let plain = hex(PLAIN);
+        assert_eq!(magma.mac(&plain, 4).unwrap(), hex("154e7210"));
+        assert!(magma.verify_mac(&plain, &hex("154e7210")));
+        assert!(!magma.verify_mac(&plain[1..], &hex("154e7210")));
+    }
+}
@@ -1,5 +1,45 @@
+mod error;
+mod padding;
+
 pub mod blowfish;
 pub mod gost;
+pub mod modes;
+pub mod stream;
+pub mod container;
+pub mod sha256;
+pub mod kdf;
+pub mod bcrypt;
+pub mod aead;
+pub mod cmac;
+pub mod twofish;
+pub mod kuznyechik;
+pub mod magma;
+pub mod rotate;
+
+#[cfg(test)]
+mod vectors;
+
+pub use error::CryptoError;
+pub use padding::Padding;
+
+/// Common interface of the block ciphers (Blowfish, Gost).
+/// Modes of operation are written once on top of it (see `modes`).
+/// Ciphers are shared between threads by parallel modes.
+pub trait BlockCipher: Sized + Sync {
+    /// Number of bytes in one block.
+    const BLOCK_SIZE: usize;
+    /// Block of bytes ([u8; BLOCK_SIZE]).
+    type Block: Copy + Default + AsRef<[u8]> + AsMut<[u8]>;
+
+    /// Creates cipher-object for passed key.
+    fn new(key: &[u8]) -> Result<Self, CryptoError>;
+
+    /// Encrypts one block in place.
+    fn encrypt_block(&self, block: &mut Self::Block);
+
+    /// Decrypts one block in place.
+    fn decrypt_block(&self, block: &mut Self::Block);
+}
 
 /// The number of bytes to encrypt must be a multiple of the block size.
 /// If not, add padding.
@@ -13,25 +53,119 @@ fn align_to_block(input: &[u8], block_size: usize) -> Vec<u8> {
     buffer
 }
 
-fn zeroed_buffer(nbytes: usize) -> Vec<u8> {
-    let mut buffer = Vec::new();
-    buffer.resize(nbytes, 0);
-    buffer
+/// Order of bytes in 32 bit words of a block (and of GOST key).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Little-endian words (format used by this crate since the beginning).
+    #[default]
+    LittleEndian,
+    /// Big-endian words (reference implementations, OpenSSL, GOST R 34.12-2015).
+    BigEndian,
+}
+
+impl ByteOrder {
+    /// Converts bytes from passed data buffer to block (tuple of 2xu32).
+    fn read_block(&self, data: &[u8]) -> (u32, u32) {
+        match self {
+            ByteOrder::LittleEndian => bytes2block(data),
+            ByteOrder::BigEndian => (
+                u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            ),
+        }
+    }
+
+    /// Converts block (tuple of 2xu32) to bytes saved in passed data buffer.
+    fn write_block(&self, x: (u32, u32), data: &mut [u8]) {
+        match self {
+            ByteOrder::LittleEndian => block2bytes(x, data),
+            ByteOrder::BigEndian => {
+                data[..4].copy_from_slice(&x.0.to_be_bytes());
+                data[4..8].copy_from_slice(&x.1.to_be_bytes());
+            }
+        }
+    }
+}
+
+/// Copies bytes from passed slice to cipher's block.
+fn load<C: BlockCipher>(data: &[u8]) -> C::Block {
+    let mut block = C::Block::default();
+    block.as_mut().copy_from_slice(&data[..C::BLOCK_SIZE]);
+    block
+}
+
+/// XORs passed bytes into block.
+fn xor_into(block: &mut [u8], data: &[u8]) {
+    for (b, d) in block.iter_mut().zip(data) {
+        *b ^= d;
+    }
+}
+
+/// Creates counter block for passed block index.
+/// The index is added to the nonce treated as big-endian number.
+fn counter_block<C: BlockCipher>(nonce: &[u8], index: u64) -> C::Block {
+    let mut block = load::<C>(nonce);
+    let bytes = block.as_mut();
+    let mut carry = index as u128;
+    for b in bytes.iter_mut().rev() {
+        if carry == 0 {
+            break;
+        }
+        let sum = (*b as u128) + (carry & 0xff);
+        *b = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+    block
 }
 
 fn random_bytes(n: usize) -> Vec<u8> {
-    let mut buffer = Vec::with_capacity(n);
-    buffer.resize(n, 0);
+    let mut buffer = vec![0u8; n];
 
     use rand::Rng;
     rand::thread_rng().fill(&mut buffer[..]);
     buffer
 }
 
+/// Overwrites passed values with zeros (e.g. key material).
+/// Volatile writes are never removed by the optimizer,
+/// even if the memory isn't read again.
+fn zeroize<T: Copy + Default>(data: &mut [T]) {
+    for x in data.iter_mut() {
+        // SAFETY: `x` is a valid, aligned and exclusive reference.
+        unsafe { std::ptr::write_volatile(x, T::default()) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+/// Returns `index`-th value reading all of them, so the memory access
+/// pattern doesn't depend on (secret) index (cache-timing resistant lookup).
+fn ct_select<I: Iterator<Item = u32>>(values: I, index: usize) -> u32 {
+    let mut v = 0u32;
+    for (i, x) in values.enumerate() {
+        let d = (i ^ index) as u32;
+        // 0xffffffff if d == 0, 0 otherwise (no branches)
+        let mask = ((d | d.wrapping_neg()) >> 31).wrapping_sub(1);
+        v |= x & mask;
+    }
+    v
+}
+
+/// Compares two byte slices in time independent of their content.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b) {
+        diff |= x ^ y;
+    }
+    // black_box prevents the compiler from short-circuiting the loop
+    std::hint::black_box(diff) == 0
+}
+
 /// Creates padding vector.
 fn padding(nbytes: usize) -> Vec<u8> {
-    let mut pad: Vec<u8> = Vec::with_capacity(nbytes);
-    pad.resize(nbytes, 0);
+    let mut pad = vec![0u8; nbytes];
     pad[0] = 128;
     pad
 }
@@ -84,3 +218,44 @@ fn bytes2block(data: &[u8]) -> (u32, u32) {
         (data[4] as u32);
     (xl, xr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random and boundary 32 bit words, inputs of tests comparing
+    /// hardened (`constant-time` feature) functions with the table ones.
+    pub fn sample_words() -> impl Iterator<Item = u32> {
+        std::iter::successors(Some(0x01234567u32), |x| Some(x.wrapping_mul(0x9e3779b9).rotate_left(7) ^ 0x5a5a5a5a))
+            .take(1000)
+            .chain([0, 0xff, 0xff00ff00, 0xffffffff])
+    }
+
+    #[test]
+    fn test_ct_select() {
+        let values: Vec<u32> = sample_words().take(256).collect();
+        for (i, &v) in values.iter().enumerate() {
+            assert_eq!(ct_select(values.iter().copied(), i), v);
+        }
+        assert_eq!(ct_select(values.iter().copied(), 256), 0);
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let mut key = *b"Trzy po trzy";
+        zeroize(&mut key);
+        assert_eq!(key, [0u8; 12]);
+
+        let mut words = vec![u32::MAX; 18];
+        zeroize(&mut words);
+        assert!(words.iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
@@ -0,0 +1,695 @@
+//! Modes of operation written once for every `BlockCipher`.
+
+use crate::crypto::*;
+
+/// Encrypts passed plain text (ECB mode).
+/// Legacy padding (0x80 0x00.. only when needed) is added.
+pub fn encrypt_ecb<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+    Ok(ecb_encrypt_blocks(cipher, align_to_block(input, C::BLOCK_SIZE), &Parallel::SEQUENTIAL))
+}
+
+/// Decrypts passed cipher text (ECB mode).
+/// Legacy padding is removed if found.
+pub fn decrypt_ecb<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    Ok(unpad(ecb_decrypt_blocks(cipher, input, &Parallel::SEQUENTIAL)?))
+}
+
+/// Encrypts passed plain text with selected padding (ECB mode).
+pub fn encrypt_ecb_with<C: BlockCipher>(cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+    Ok(ecb_encrypt_blocks(cipher, padding.pad(input, C::BLOCK_SIZE)?, &Parallel::SEQUENTIAL))
+}
+
+/// Decrypts passed cipher text and checks selected padding (ECB mode).
+pub fn decrypt_ecb_with<C: BlockCipher>(cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+    let data = ecb_decrypt_blocks(cipher, input, &Parallel::SEQUENTIAL)?;
+    Ok(padding.unpad(&data, C::BLOCK_SIZE)?.to_vec())
+}
+
+/// Encrypts passed plain text (CBC mode).
+/// Before encryption creates random IV vector.
+pub fn encrypt_cbc<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    encrypt_cbc_iv(cipher, input, &random_bytes(C::BLOCK_SIZE))
+}
+
+/// Encrypts plain text with passed IV vector (CBC mode).
+/// IV vector is saved as the first block of the result.
+/// Legacy padding (0x80 0x00.. only when needed) is added.
+pub fn encrypt_cbc_iv<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    check_cbc_input::<C>(input, iv)?;
+    Ok(cbc_encrypt_blocks(cipher, &align_to_block(input, C::BLOCK_SIZE), iv))
+}
+
+/// Decrypts passed cipher text (CBC mode).
+/// The first block of cipher text is IV vector.
+/// Legacy padding is removed if found.
+pub fn decrypt_cbc<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    Ok(unpad(cbc_decrypt_blocks(cipher, input, &Parallel::SEQUENTIAL)?))
+}
+
+/// Encrypts passed plain text with selected padding (CBC mode).
+/// Before encryption creates random IV vector.
+pub fn encrypt_cbc_with<C: BlockCipher>(cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+    encrypt_cbc_iv_with(cipher, input, &random_bytes(C::BLOCK_SIZE), padding)
+}
+
+/// Encrypts plain text with passed IV vector and selected padding (CBC mode).
+pub fn encrypt_cbc_iv_with<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+    check_cbc_input::<C>(input, iv)?;
+    Ok(cbc_encrypt_blocks(cipher, &padding.pad(input, C::BLOCK_SIZE)?, iv))
+}
+
+/// Decrypts passed cipher text and checks selected padding (CBC mode).
+pub fn decrypt_cbc_with<C: BlockCipher>(cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+    let data = cbc_decrypt_blocks(cipher, input, &Parallel::SEQUENTIAL)?;
+    Ok(padding.unpad(&data, C::BLOCK_SIZE)?.to_vec())
+}
+
+/// Ciphertext stealing variants of CBC mode (NIST SP 800-38A Addendum).
+/// They differ only in the order of the last two cipher blocks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cts {
+    /// Partial block before the last full block.
+    Cs1,
+    /// Like CS1 for aligned input, otherwise like CS3.
+    Cs2,
+    /// The last two blocks are always swapped (Kerberos).
+    Cs3,
+}
+
+impl Cts {
+    /// Checks if the last two blocks are swapped (against CS1).
+    fn swapped(&self, partial: bool) -> bool {
+        match self {
+            Cts::Cs1 => false,
+            Cts::Cs2 => partial,
+            Cts::Cs3 => true,
+        }
+    }
+}
+
+/// Encrypts passed plain text with ciphertext stealing (CBC mode).
+/// Before encryption creates random IV vector.
+pub fn encrypt_cbc_cts<C: BlockCipher>(cipher: &C, input: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+    encrypt_cbc_cts_iv(cipher, input, &random_bytes(C::BLOCK_SIZE), variant)
+}
+
+/// Encrypts plain text with passed IV vector and ciphertext stealing (CBC mode).
+/// Cipher text (without IV vector) is exactly as long as plain text,
+/// which must be at least one block long.
+pub fn encrypt_cbc_cts_iv<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+    check_cbc_input::<C>(input, iv)?;
+    if input.len() < C::BLOCK_SIZE {
+        return Err(CryptoError::InvalidParameter("input is shorter than block"));
+    }
+
+    let bs = C::BLOCK_SIZE;
+    let mut data = cbc_encrypt_blocks(cipher, &Padding::Zero.pad(input, bs)?, iv);
+    let nbytes = input.len();
+    let rest = nbytes % bs;
+    let m = if rest == 0 { bs } else { rest };
+    if nbytes > bs {
+        // drop stolen bytes of the last but one block
+        let start = nbytes - m;
+        data.drain(start + m..start + bs);
+        if variant.swapped(rest != 0) {
+            let len = data.len();
+            data[len - bs - m..].rotate_left(m);
+        }
+    }
+    Ok(data)
+}
+
+/// Decrypts passed cipher text with ciphertext stealing (CBC mode).
+/// The first block of cipher text is IV vector.
+pub fn decrypt_cbc_cts<C: BlockCipher>(cipher: &C, input: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+    let bs = C::BLOCK_SIZE;
+    if input.len() < 2 * bs {
+        return Err(CryptoError::TruncatedCiphertext { block_size: bs, actual: input.len() });
+    }
+
+    let nbytes = input.len() - bs;
+    let rest = nbytes % bs;
+    let m = if rest == 0 { bs } else { rest };
+    let mut data = input.to_vec();
+    if nbytes > bs {
+        let len = data.len();
+        if variant.swapped(rest != 0) {
+            data[len - bs - m..].rotate_right(m);
+        }
+        if rest != 0 {
+            // restore stolen bytes: D(C[n]) = (P[n] || 0) ^ C[n-1]
+            let mut z = load::<C>(&data[len - bs..]);
+            cipher.decrypt_block(&mut z);
+            let tail = data.split_off(len - bs);
+            data.extend_from_slice(&z.as_ref()[m..]);
+            data.extend_from_slice(&tail);
+        }
+    }
+
+    let mut plain = cbc_decrypt_blocks(cipher, &data, &Parallel::SEQUENTIAL)?;
+    plain.truncate(nbytes);
+    Ok(plain)
+}
+
+/// Removes legacy padding (if found) from decrypted bytes.
+fn unpad(mut plain: Vec<u8>) -> Vec<u8> {
+    if let Some(idx) = padding_index(&plain) {
+        plain.truncate(idx);
+    }
+    plain
+}
+
+/// Encrypts aligned data in place (ECB mode).
+fn ecb_encrypt_blocks<C: BlockCipher>(cipher: &C, mut data: Vec<u8>, par: &Parallel) -> Vec<u8> {
+    par.run(&mut data, C::BLOCK_SIZE, |_, part| {
+        for chunk in part.chunks_mut(C::BLOCK_SIZE) {
+            let mut block = load::<C>(chunk);
+            cipher.encrypt_block(&mut block);
+            chunk.copy_from_slice(block.as_ref());
+        }
+    });
+    data
+}
+
+/// Decrypts cipher text (ECB mode), padding is not removed.
+fn ecb_decrypt_blocks<C: BlockCipher>(cipher: &C, input: &[u8], par: &Parallel) -> Result<Vec<u8>, CryptoError> {
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+    if !input.len().is_multiple_of(C::BLOCK_SIZE) {
+        return Err(CryptoError::TruncatedCiphertext { block_size: C::BLOCK_SIZE, actual: input.len() });
+    }
+
+    let mut data = input.to_vec();
+    par.run(&mut data, C::BLOCK_SIZE, |_, part| {
+        for chunk in part.chunks_mut(C::BLOCK_SIZE) {
+            let mut block = load::<C>(chunk);
+            cipher.decrypt_block(&mut block);
+            chunk.copy_from_slice(block.as_ref());
+        }
+    });
+    Ok(data)
+}
+
+fn check_cbc_input<C: BlockCipher>(input: &[u8], iv: &[u8]) -> Result<(), CryptoError> {
+    if iv.len() != C::BLOCK_SIZE {
+        return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE, actual: iv.len() });
+    }
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+    Ok(())
+}
+
+/// Encrypts aligned plain text (CBC mode).
+/// Returns IV vector followed by cipher text.
+fn cbc_encrypt_blocks<C: BlockCipher>(cipher: &C, plain: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(plain.len() + C::BLOCK_SIZE);
+    data.extend_from_slice(iv);
+
+    let mut x = load::<C>(iv);
+    for chunk in plain.chunks(C::BLOCK_SIZE) {
+        xor_into(x.as_mut(), chunk);
+        cipher.encrypt_block(&mut x);
+        data.extend_from_slice(x.as_ref());
+    }
+    data
+}
+
+/// Decrypts cipher text (CBC mode), padding is not removed.
+/// Every block depends only on two cipher blocks, so decryption
+/// (unlike encryption) can be split between threads.
+fn cbc_decrypt_blocks<C: BlockCipher>(cipher: &C, input: &[u8], par: &Parallel) -> Result<Vec<u8>, CryptoError> {
+    let nbytes = input.len();
+    if nbytes < 2 * C::BLOCK_SIZE || !nbytes.is_multiple_of(C::BLOCK_SIZE) {
+        return Err(CryptoError::TruncatedCiphertext { block_size: C::BLOCK_SIZE, actual: nbytes });
+    }
+
+    let mut data = input[C::BLOCK_SIZE..].to_vec();
+    par.run(&mut data, C::BLOCK_SIZE, |offset, part| {
+        // input starts with IV, so the same offset gives the previous cipher block
+        let previous = input[offset..].chunks(C::BLOCK_SIZE);
+        for (chunk, p) in part.chunks_mut(C::BLOCK_SIZE).zip(previous) {
+            let mut x = load::<C>(chunk);
+            cipher.decrypt_block(&mut x);
+            xor_into(x.as_mut(), p);
+            chunk.copy_from_slice(x.as_ref());
+        }
+    });
+    Ok(data)
+}
+
+/// Encrypts passed plain text (CTR mode).
+/// Before encryption creates random nonce.
+pub fn encrypt_ctr<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    encrypt_ctr_nonce(cipher, input, &random_bytes(C::BLOCK_SIZE))
+}
+
+/// Encrypts plain text with passed nonce (CTR mode).
+/// Nonce is saved as the first block of the result, no padding is added.
+pub fn encrypt_ctr_nonce<C: BlockCipher>(cipher: &C, input: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    Parallel::SEQUENTIAL.encrypt_ctr_nonce(cipher, input, nonce)
+}
+
+/// Decrypts passed cipher text (CTR mode).
+/// The first block of cipher text is the nonce.
+pub fn decrypt_ctr<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    Parallel::SEQUENTIAL.decrypt_ctr(cipher, input)
+}
+
+/// XORs passed data in place with the key stream starting at byte `offset`.
+/// Encryption and decryption are the same operation, so any part
+/// of the stream can be processed without touching the rest.
+pub fn apply_ctr<C: BlockCipher>(cipher: &C, nonce: &[u8], offset: u64, data: &mut [u8]) -> Result<(), CryptoError> {
+    Parallel::SEQUENTIAL.apply_ctr(cipher, nonce, offset, data)
+}
+
+/// XORs data with the key stream, nonce and range of offsets are already checked.
+fn ctr_blocks<C: BlockCipher>(cipher: &C, nonce: &[u8], offset: u64, data: &mut [u8]) {
+    let bs = C::BLOCK_SIZE as u64;
+    let mut index = offset / bs;
+    let mut skip = (offset % bs) as usize;
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut gamma = counter_block::<C>(nonce, index);
+        cipher.encrypt_block(&mut gamma);
+        let n = (C::BLOCK_SIZE - skip).min(data.len() - pos);
+        xor_into(&mut data[pos..pos + n], &gamma.as_ref()[skip..]);
+        pos += n;
+        skip = 0;
+        index += 1;
+    }
+}
+
+/// Multi-threaded processing of modes with independent blocks:
+/// ECB, CTR and CBC decryption. Results are identical to the
+/// single-threaded functions of this module.
+///
+/// Data is split into one chunk per thread; buffers shorter than
+/// the threshold are processed in the calling thread.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Parallel {
+    threads: usize,
+    threshold: usize,
+}
+
+/// Default minimal number of bytes processed by many threads (1 MiB).
+pub const PARALLEL_THRESHOLD: usize = 1 << 20;
+
+impl Default for Parallel {
+    fn default() -> Self {
+        Parallel::new()
+    }
+}
+
+impl Parallel {
+    /// Processing in the calling thread only.
+    pub const SEQUENTIAL: Parallel = Parallel { threads: 1, threshold: usize::MAX };
+
+    /// Uses all available CPUs for buffers of at least `PARALLEL_THRESHOLD` bytes.
+    pub fn new() -> Parallel {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Parallel { threads, threshold: PARALLEL_THRESHOLD }
+    }
+
+    /// Sets number of threads (at least 1).
+    pub fn with_threads(mut self, threads: usize) -> Parallel {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets minimal number of bytes processed by many threads.
+    pub fn with_threshold(mut self, threshold: usize) -> Parallel {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Encrypts passed plain text with legacy padding (ECB mode).
+    pub fn encrypt_ecb<C: BlockCipher>(&self, cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if input.is_empty() {
+            return Err(CryptoError::EmptyInput);
+        }
+        Ok(ecb_encrypt_blocks(cipher, align_to_block(input, C::BLOCK_SIZE), self))
+    }
+
+    /// Decrypts passed cipher text, legacy padding is removed if found (ECB mode).
+    pub fn decrypt_ecb<C: BlockCipher>(&self, cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        Ok(unpad(ecb_decrypt_blocks(cipher, input, self)?))
+    }
+
+    /// Encrypts passed plain text with selected padding (ECB mode).
+    pub fn encrypt_ecb_with<C: BlockCipher>(&self, cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        if input.is_empty() {
+            return Err(CryptoError::EmptyInput);
+        }
+        Ok(ecb_encrypt_blocks(cipher, padding.pad(input, C::BLOCK_SIZE)?, self))
+    }
+
+    /// Decrypts passed cipher text and checks selected padding (ECB mode).
+    pub fn decrypt_ecb_with<C: BlockCipher>(&self, cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        let data = ecb_decrypt_blocks(cipher, input, self)?;
+        Ok(padding.unpad(&data, C::BLOCK_SIZE)?.to_vec())
+    }
+
+    /// Decrypts passed cipher text, legacy padding is removed if found (CBC mode).
+    /// The first block of cipher text is IV vector.
+    pub fn decrypt_cbc<C: BlockCipher>(&self, cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        Ok(unpad(cbc_decrypt_blocks(cipher, input, self)?))
+    }
+
+    /// Decrypts passed cipher text and checks selected padding (CBC mode).
+    pub fn decrypt_cbc_with<C: BlockCipher>(&self, cipher: &C, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        let data = cbc_decrypt_blocks(cipher, input, self)?;
+        Ok(padding.unpad(&data, C::BLOCK_SIZE)?.to_vec())
+    }
+
+    /// Encrypts plain text with passed nonce (CTR mode).
+    /// Nonce is saved as the first block of the result.
+    pub fn encrypt_ctr_nonce<C: BlockCipher>(&self, cipher: &C, input: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if input.is_empty() {
+            return Err(CryptoError::EmptyInput);
+        }
+
+        let mut data = Vec::with_capacity(input.len() + C::BLOCK_SIZE);
+        data.extend_from_slice(nonce);
+        data.extend_from_slice(input);
+        self.apply_ctr(cipher, nonce, 0, &mut data[C::BLOCK_SIZE..])?;
+        Ok(data)
+    }
+
+    /// Decrypts passed cipher text (CTR mode).
+    /// The first block of cipher text is the nonce.
+    pub fn decrypt_ctr<C: BlockCipher>(&self, cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if input.len() <= C::BLOCK_SIZE {
+            return Err(CryptoError::TruncatedCiphertext { block_size: C::BLOCK_SIZE, actual: input.len() });
+        }
+
+        let mut data = input[C::BLOCK_SIZE..].to_vec();
+        self.apply_ctr(cipher, &input[..C::BLOCK_SIZE], 0, &mut data)?;
+        Ok(data)
+    }
+
+    /// XORs passed data in place with the key stream starting at byte `offset` (CTR mode).
+    /// Data must end within 2^64 bytes of the key stream.
+    pub fn apply_ctr<C: BlockCipher>(&self, cipher: &C, nonce: &[u8], offset: u64, data: &mut [u8]) -> Result<(), CryptoError> {
+        if nonce.len() != C::BLOCK_SIZE {
+            return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE, actual: nonce.len() });
+        }
+        // so neither offset + pos nor block index in `ctr_blocks` overflow
+        if offset.checked_add(data.len() as u64).is_none() {
+            return Err(CryptoError::InvalidParameter("offset is beyond the key stream"));
+        }
+        self.run(data, C::BLOCK_SIZE, |pos, part| ctr_blocks(cipher, nonce, offset + pos as u64, part));
+        Ok(())
+    }
+
+    /// Calls `f(offset, chunk)` for chunks of data (whole blocks, except the last one).
+    fn run<F>(&self, data: &mut [u8], block_size: usize, f: F)
+        where F: Fn(usize, &mut [u8]) + Sync
+    {
+        if self.threads == 1 || data.len() < self.threshold || data.len() <= block_size {
+            f(0, data);
+            return;
+        }
+
+        let nblocks = data.len().div_ceil(block_size);
+        let chunk_size = nblocks.div_ceil(self.threads) * block_size;
+        let f = &f;
+        std::thread::scope(|scope| {
+            for (i, chunk) in data.chunks_mut(chunk_size).enumerate() {
+                scope.spawn(move || f(i * chunk_size, chunk));
+            }
+        });
+    }
+}
+
+/// Encrypts passed plain text (CFB mode).
+/// Before encryption creates random IV vector.
+pub fn encrypt_cfb<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    encrypt_cfb_iv(cipher, input, &random_bytes(C::BLOCK_SIZE))
+}
+
+/// Encrypts plain text with passed IV vector (CFB mode, full block feedback).
+/// IV vector is saved as the first block of the result, no padding is added.
+pub fn encrypt_cfb_iv<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if iv.len() != C::BLOCK_SIZE {
+        return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE, actual: iv.len() });
+    }
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+
+    let mut data = Vec::with_capacity(input.len() + C::BLOCK_SIZE);
+    data.extend_from_slice(iv);
+
+    let mut x = load::<C>(iv);
+    for chunk in input.chunks(C::BLOCK_SIZE) {
+        cipher.encrypt_block(&mut x);
+        xor_into(x.as_mut(), chunk);
+        data.extend_from_slice(&x.as_ref()[..chunk.len()]);
+    }
+    Ok(data)
+}
+
+/// Decrypts passed cipher text (CFB mode).
+/// The first block of cipher text is IV vector.
+pub fn decrypt_cfb<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if input.len() <= C::BLOCK_SIZE {
+        return Err(CryptoError::TruncatedCiphertext { block_size: C::BLOCK_SIZE, actual: input.len() });
+    }
+
+    let mut data = Vec::with_capacity(input.len() - C::BLOCK_SIZE);
+    let mut x = load::<C>(input);
+    for chunk in input[C::BLOCK_SIZE..].chunks(C::BLOCK_SIZE) {
+        cipher.encrypt_block(&mut x);
+        let mut plain = x;
+        xor_into(plain.as_mut(), chunk);
+        data.extend_from_slice(&plain.as_ref()[..chunk.len()]);
+        x.as_mut()[..chunk.len()].copy_from_slice(chunk);
+    }
+    Ok(data)
+}
+
+/// Encrypts passed plain text (OFB mode).
+/// Before encryption creates random IV vector.
+pub fn encrypt_ofb<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    encrypt_ofb_iv(cipher, input, &random_bytes(C::BLOCK_SIZE))
+}
+
+/// Encrypts plain text with passed IV vector (OFB mode).
+/// IV vector is saved as the first block of the result, no padding is added.
+pub fn encrypt_ofb_iv<C: BlockCipher>(cipher: &C, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if iv.len() != C::BLOCK_SIZE {
+        return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE, actual: iv.len() });
+    }
+    if input.is_empty() {
+        return Err(CryptoError::EmptyInput);
+    }
+
+    let mut data = Vec::with_capacity(input.len() + C::BLOCK_SIZE);
+    data.extend_from_slice(iv);
+    data.extend_from_slice(input);
+    ofb(cipher, iv, &mut data[C::BLOCK_SIZE..]);
+    Ok(data)
+}
+
+/// Decrypts passed cipher text (OFB mode).
+/// The first block of cipher text is IV vector.
+pub fn decrypt_ofb<C: BlockCipher>(cipher: &C, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if input.len() <= C::BLOCK_SIZE {
+        return Err(CryptoError::TruncatedCiphertext { block_size: C::BLOCK_SIZE, actual: input.len() });
+    }
+
+    let mut data = input[C::BLOCK_SIZE..].to_vec();
+    ofb(cipher, &input[..C::BLOCK_SIZE], &mut data);
+    Ok(data)
+}
+
+/// XORs passed data in place with OFB key stream.
+fn ofb<C: BlockCipher>(cipher: &C, iv: &[u8], data: &mut [u8]) {
+    let mut x = load::<C>(iv);
+    for chunk in data.chunks_mut(C::BLOCK_SIZE) {
+        cipher.encrypt_block(&mut x);
+        xor_into(chunk, x.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::blowfish::Blowfish;
+    use crate::crypto::gost::Gost;
+
+    fn round_trip<C: BlockCipher>(key: &[u8]) {
+        let cipher = C::new(key).unwrap();
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+
+        let encrypted = encrypt_ecb(&cipher, plain).unwrap();
+        assert_eq!(encrypted.len() % C::BLOCK_SIZE, 0);
+        assert_eq!(decrypt_ecb(&cipher, &encrypted).unwrap(), plain);
+
+        let encrypted = encrypt_cbc(&cipher, plain).unwrap();
+        assert_eq!(encrypted.len() % C::BLOCK_SIZE, 0);
+        assert_eq!(decrypt_cbc(&cipher, &encrypted).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_generic_round_trip() {
+        round_trip::<Blowfish>(b"TESTKEY");
+        round_trip::<Gost>(b"12345678901234567890123456789012");
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        assert_eq!(encrypt_cbc_iv(&gt, b"abc", &[0u8; 7]), Err(CryptoError::InvalidIv { expected: 8, actual: 7 }));
+        assert_eq!(decrypt_cbc(&gt, &[0u8; 9]), Err(CryptoError::TruncatedCiphertext { block_size: 8, actual: 9 }));
+        assert_eq!(decrypt_ecb(&gt, &[0u8; 12]), Err(CryptoError::TruncatedCiphertext { block_size: 8, actual: 12 }));
+        assert_eq!(encrypt_ctr(&gt, b""), Err(CryptoError::EmptyInput));
+        assert_eq!(Blowfish::new(b"abc").err(), Some(CryptoError::InvalidKeyLength { min: 4, max: 56, actual: 3 }));
+        assert_eq!(Gost::new(b"abc").err().unwrap().to_string(), "invalid key length: expected 32 bytes, got 3");
+    }
+
+    #[test]
+    fn test_ctr() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+
+        let encrypted = encrypt_ctr(&gt, plain).unwrap();
+        assert_eq!(encrypted.len(), plain.len() + 8);
+        assert_eq!(decrypt_ctr(&gt, &encrypted).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_ctr_seek() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let nonce = [0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe];
+        let plain = "Adam, Artur, Błażej, Kacpi, Nikoś, XY".as_bytes();
+
+        let mut whole = plain.to_vec();
+        apply_ctr(&bf, &nonce, 0, &mut whole).unwrap();
+
+        for offset in [0usize, 3, 8, 13, 30] {
+            let mut part = plain[offset..].to_vec();
+            apply_ctr(&bf, &nonce, offset as u64, &mut part).unwrap();
+            assert_eq!(part, &whole[offset..]);
+        }
+
+        // the end of the key stream
+        let mut part = plain[..8].to_vec();
+        assert!(apply_ctr(&bf, &nonce, u64::MAX - 8, &mut part).is_ok());
+        assert_eq!(apply_ctr(&bf, &nonce, u64::MAX - 7, &mut part), Err(CryptoError::InvalidParameter("offset is beyond the key stream")));
+        assert!(Parallel::new().apply_ctr(&bf, &nonce, u64::MAX, &mut part).is_err());
+    }
+
+    #[test]
+    fn test_cfb_ofb() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let iv = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        let plain = "Adam, Artur, Błażej, Kacpi, Nikoś, XY".as_bytes();
+
+        let encrypted = encrypt_cfb_iv(&bf, plain, &iv).unwrap();
+        assert_eq!(encrypted.len(), plain.len() + 8);
+        assert_eq!(decrypt_cfb(&bf, &encrypted).unwrap(), plain);
+
+        // the first block of CFB and OFB uses the same key stream
+        let other = encrypt_ofb_iv(&bf, plain, &iv).unwrap();
+        assert_eq!(other[..16], encrypted[..16]);
+        assert_ne!(other[16..], encrypted[16..]);
+        assert_eq!(decrypt_ofb(&bf, &other).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_padding() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        // legacy padding loses the trailing 0x80 0x00
+        let plain = [1u8, 2, 3, 4, 5, 6, 0x80, 0];
+        let encrypted = encrypt_cbc(&gt, &plain).unwrap();
+        assert_eq!(decrypt_cbc(&gt, &encrypted).unwrap(), &plain[..6]);
+
+        for padding in [Padding::Pkcs7, Padding::Iso7816, Padding::AnsiX923, Padding::None] {
+            let encrypted = encrypt_cbc_with(&gt, &plain, padding).unwrap();
+            assert_eq!(decrypt_cbc_with(&gt, &encrypted, padding).unwrap(), plain);
+            let encrypted = encrypt_ecb_with(&gt, &plain, padding).unwrap();
+            assert_eq!(decrypt_ecb_with(&gt, &encrypted, padding).unwrap(), plain);
+        }
+
+        // cipher text without padding is rejected
+        let encrypted = encrypt_ecb_with(&gt, &plain, Padding::None).unwrap();
+        assert_eq!(decrypt_ecb_with(&gt, &encrypted, Padding::Pkcs7), Err(CryptoError::BadPadding));
+    }
+
+    #[test]
+    fn test_cts() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let iv = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        let plain: Vec<u8> = (0..40u8).collect();
+
+        for nbytes in 8..=plain.len() {
+            let input = &plain[..nbytes];
+            let cs1 = encrypt_cbc_cts_iv(&bf, input, &iv, Cts::Cs1).unwrap();
+            let cs2 = encrypt_cbc_cts_iv(&bf, input, &iv, Cts::Cs2).unwrap();
+            let cs3 = encrypt_cbc_cts_iv(&bf, input, &iv, Cts::Cs3).unwrap();
+            assert_eq!(cs1.len(), nbytes + 8);
+
+            let m = if nbytes % 8 == 0 { 8 } else { nbytes % 8 };
+            if nbytes % 8 == 0 {
+                assert_eq!(cs1, encrypt_cbc_iv_with(&bf, input, &iv, Padding::None).unwrap());
+                assert_eq!(cs1, cs2);
+            } else {
+                assert_eq!(cs2, cs3);
+            }
+            if nbytes > 8 {
+                // CS3 = CS1 with the last two blocks swapped
+                let n = cs1.len();
+                assert_eq!(cs3[n - m - 8..n - m], cs1[n - 8..]);
+                assert_eq!(cs3[n - m..], cs1[n - 8 - m..n - 8]);
+            }
+
+            assert_eq!(decrypt_cbc_cts(&bf, &cs1, Cts::Cs1).unwrap(), input);
+            assert_eq!(decrypt_cbc_cts(&bf, &cs2, Cts::Cs2).unwrap(), input);
+            assert_eq!(decrypt_cbc_cts(&bf, &cs3, Cts::Cs3).unwrap(), input);
+        }
+
+        assert!(encrypt_cbc_cts_iv(&bf, &plain[..7], &iv, Cts::Cs1).is_err());
+        assert!(decrypt_cbc_cts(&bf, &plain[..15], Cts::Cs1).is_err());
+    }
+
+    #[test]
+    fn test_parallel() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let nonce = [9u8; 8];
+        let plain: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+
+        for threads in [1, 2, 3, 7] {
+            let par = Parallel::new().with_threads(threads).with_threshold(0);
+            for nbytes in [1, 8, 9, 63, 64, 999, 1000] {
+                let input = &plain[..nbytes];
+                let ecb = par.encrypt_ecb_with(&gt, input, Padding::Pkcs7).unwrap();
+                assert_eq!(ecb, encrypt_ecb_with(&gt, input, Padding::Pkcs7).unwrap());
+                assert_eq!(par.decrypt_ecb_with(&gt, &ecb, Padding::Pkcs7).unwrap(), input);
+                assert_eq!(par.encrypt_ecb(&gt, input).unwrap(), encrypt_ecb(&gt, input).unwrap());
+
+                let cbc = encrypt_cbc_with(&gt, input, Padding::Pkcs7).unwrap();
+                assert_eq!(par.decrypt_cbc_with(&gt, &cbc, Padding::Pkcs7).unwrap(), input);
+                assert_eq!(par.decrypt_cbc(&gt, &cbc).unwrap(), decrypt_cbc(&gt, &cbc).unwrap());
+
+                let ctr = par.encrypt_ctr_nonce(&gt, input, &nonce).unwrap();
+                assert_eq!(ctr, encrypt_ctr_nonce(&gt, input, &nonce).unwrap());
+                assert_eq!(par.decrypt_ctr(&gt, &ctr).unwrap(), input);
+
+                let mut part = input.to_vec();
+                par.apply_ctr(&gt, &nonce, 5, &mut part).unwrap();
+                let mut expected = input.to_vec();
+                apply_ctr(&gt, &nonce, 5, &mut expected).unwrap();
+                assert_eq!(part, expected);
+            }
+        }
+    }
+}
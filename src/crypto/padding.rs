@@ -0,0 +1,127 @@
+use crate::crypto::CryptoError;
+
+/// Padding schemes of block cipher modes (ECB, CBC).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Padding {
+    /// Every padding byte holds the number of padding bytes (RFC 5652).
+    Pkcs7,
+    /// 0x80 followed by zeros (ISO/IEC 7816-4).
+    Iso7816,
+    /// Zeros, the last byte holds the number of padding bytes (ANSI X9.23).
+    AnsiX923,
+    /// Zeros, only when needed. Trailing zeros of plain text are lost.
+    Zero,
+    /// No padding, input must be a multiple of the block size.
+    None,
+}
+
+impl Padding {
+    /// Returns copy of data padded to multiple of the block size.
+    /// Pkcs7, Iso7816 and AnsiX923 always add at least one byte.
+    pub fn pad(&self, data: &[u8], block_size: usize) -> Result<Vec<u8>, CryptoError> {
+        let rest = data.len() % block_size;
+        let nbytes = match self {
+            Padding::Pkcs7 | Padding::Iso7816 | Padding::AnsiX923 => block_size - rest,
+            Padding::Zero => (block_size - rest) % block_size,
+            Padding::None if rest != 0 => {
+                return Err(CryptoError::InvalidParameter("input is not a multiple of block size"));
+            }
+            Padding::None => 0,
+        };
+
+        let mut buffer = Vec::with_capacity(data.len() + nbytes);
+        buffer.extend_from_slice(data);
+        buffer.resize(data.len() + nbytes, 0);
+        let last = buffer.len().saturating_sub(1);
+        match self {
+            Padding::Pkcs7 => buffer[data.len()..].fill(nbytes as u8),
+            Padding::Iso7816 => buffer[data.len()] = 0x80,
+            Padding::AnsiX923 => buffer[last] = nbytes as u8,
+            Padding::Zero | Padding::None => (),
+        }
+        Ok(buffer)
+    }
+
+    /// Returns data without padding.
+    /// Malformed padding is reported as `CryptoError::BadPadding`.
+    pub fn unpad<'a>(&self, data: &'a [u8], block_size: usize) -> Result<&'a [u8], CryptoError> {
+        if !data.len().is_multiple_of(block_size) {
+            return Err(CryptoError::TruncatedCiphertext { block_size, actual: data.len() });
+        }
+        let nbytes = data.len();
+
+        match self {
+            Padding::Pkcs7 | Padding::AnsiX923 => {
+                let n = *data.last().ok_or(CryptoError::BadPadding)? as usize;
+                if n == 0 || n > block_size {
+                    return Err(CryptoError::BadPadding);
+                }
+                let filler = match self {
+                    Padding::Pkcs7 => n as u8,
+                    _ => 0,
+                };
+                if data[nbytes - n..nbytes - 1].iter().any(|b| *b != filler) {
+                    return Err(CryptoError::BadPadding);
+                }
+                Ok(&data[..nbytes - n])
+            }
+            Padding::Iso7816 => {
+                // the marker must be in the last block
+                for i in (nbytes.saturating_sub(block_size)..nbytes).rev() {
+                    match data[i] {
+                        0 => continue,
+                        0x80 => return Ok(&data[..i]),
+                        _ => break,
+                    }
+                }
+                Err(CryptoError::BadPadding)
+            }
+            Padding::Zero => {
+                let mut n = nbytes;
+                while n > nbytes.saturating_sub(block_size - 1) && data[n - 1] == 0 {
+                    n -= 1;
+                }
+                Ok(&data[..n])
+            }
+            Padding::None => Ok(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pad() {
+        let data = [1u8, 2, 3];
+        assert_eq!(Padding::Pkcs7.pad(&data, 8).unwrap(), [1, 2, 3, 5, 5, 5, 5, 5]);
+        assert_eq!(Padding::Iso7816.pad(&data, 8).unwrap(), [1, 2, 3, 0x80, 0, 0, 0, 0]);
+        assert_eq!(Padding::AnsiX923.pad(&data, 8).unwrap(), [1, 2, 3, 0, 0, 0, 0, 5]);
+        assert_eq!(Padding::Zero.pad(&data, 8).unwrap(), [1, 2, 3, 0, 0, 0, 0, 0]);
+        assert!(Padding::None.pad(&data, 8).is_err());
+
+        // aligned data gets a whole block of padding
+        assert_eq!(Padding::Pkcs7.pad(&[0u8; 8], 8).unwrap().len(), 16);
+        assert_eq!(Padding::Zero.pad(&[0u8; 8], 8).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_unpad() {
+        let data = [7u8, 0x80, 0, 0, 0, 0, 0, 0];
+        for padding in [Padding::Pkcs7, Padding::Iso7816, Padding::AnsiX923, Padding::Zero, Padding::None] {
+            let padded = padding.pad(&data, 8).unwrap();
+            assert_eq!(padding.unpad(&padded, 8).unwrap(), match padding {
+                Padding::Zero => &data[..2],
+                _ => &data[..],
+            });
+        }
+
+        assert_eq!(Padding::Pkcs7.unpad(&[1, 2, 3, 4, 5, 6, 3, 3], 8), Err(CryptoError::BadPadding));
+        assert_eq!(Padding::Pkcs7.unpad(&[1, 2, 3, 4, 5, 6, 7, 9], 8), Err(CryptoError::BadPadding));
+        assert_eq!(Padding::AnsiX923.unpad(&[1, 2, 3, 4, 5, 1, 0, 3], 8), Err(CryptoError::BadPadding));
+        assert_eq!(Padding::Iso7816.unpad(&[1, 2, 3, 4, 5, 6, 7, 8], 8), Err(CryptoError::BadPadding));
+        assert_eq!(Padding::Iso7816.unpad(&[0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 8), Err(CryptoError::BadPadding));
+        assert_eq!(Padding::Pkcs7.unpad(&[], 8), Err(CryptoError::BadPadding));
+    }
+}
@@ -0,0 +1,305 @@
+//! Key rotation: re-encryption of cipher text under another cipher, key or mode.
+//!
+//! Data is streamed through `DecryptReader` and `EncryptWriter`, so memory use
+//! does not depend on the size of data. Source data (IV vector and cipher text)
+//! must be authenticated with HMAC-SHA256: the tag is checked in a first pass
+//! over the input, before anything is decrypted or written, and once more while
+//! re-encrypting (the input could change between the passes). The tag of the new
+//! data is returned for the next rotation.
+//!
+//! If the second check or reading fails, the output is incomplete and must be
+//! discarded (it ends with the last complete block, without padding).
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use crate::crypto::*;
+use crate::crypto::sha256::{HmacSha256, Sha256, DIGEST_SIZE};
+use crate::crypto::stream::{DecryptReader, EncryptWriter, StreamMode};
+
+// number of bytes processed at once
+const CHUNK_SIZE: usize = 4096;
+
+/// Result of re-encryption.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// Number of plain text bytes.
+    pub nbytes: u64,
+    /// SHA-256 of plain text.
+    pub digest: [u8; DIGEST_SIZE],
+    /// HMAC-SHA256 of the new IV vector and cipher text.
+    pub tag: [u8; DIGEST_SIZE],
+}
+
+/// Re-encryption from one cipher/key/mode to another.
+/// By default CBC uses legacy padding (as `modes::encrypt_cbc`).
+pub struct Rotation<'a, D: BlockCipher, E: BlockCipher> {
+    from: &'a D,
+    from_mode: StreamMode,
+    from_padding: Option<Padding>,
+    source_key: &'a [u8],
+    to: &'a E,
+    to_mode: StreamMode,
+    to_padding: Option<Padding>,
+    target_key: &'a [u8],
+}
+
+impl<'a, D: BlockCipher, E: BlockCipher> Rotation<'a, D, E> {
+    /// Creates re-encryption of data produced by `from` in `from_mode`
+    /// into data of `to` in `to_mode`. `source_key` checks HMAC-SHA256
+    /// of the source data, `target_key` signs the new data.
+    pub fn new(from: &'a D, from_mode: StreamMode, source_key: &'a [u8], to: &'a E, to_mode: StreamMode, target_key: &'a [u8]) -> Self {
+        Rotation { from, from_mode, from_padding: None, source_key, to, to_mode, to_padding: None, target_key }
+    }
+
+    /// Selects padding of the source and of the new cipher text (CBC mode).
+    pub fn with_padding(mut self, from: Padding, to: Padding) -> Self {
+        self.from_padding = Some(from);
+        self.to_padding = Some(to);
+        self
+    }
+
+    /// Checks `tag` of source data read from `input` and writes re-encrypted
+    /// data (new random IV vector and cipher text) to `output`.
+    pub fn run<R: Read + Seek, W: Write>(&self, input: R, tag: &[u8], output: W) -> io::Result<Report> {
+        self.run_with_iv(input, tag, output, &random_bytes(E::BLOCK_SIZE))
+    }
+
+    /// Re-encrypts data with passed IV vector (nonce) of the new cipher text.
+    pub fn run_with_iv<R: Read + Seek, W: Write>(&self, mut input: R, tag: &[u8], output: W, iv: &[u8]) -> io::Result<Report> {
+        if self.source_key.is_empty() || self.target_key.is_empty() {
+            return Err(CryptoError::InvalidParameter("MAC key is empty").into());
+        }
+
+        // the first pass: nothing is decrypted before the source is authenticated
+        let start = input.stream_position()?;
+        let mut source = MacReader { inner: input, hmac: HmacSha256::new(self.source_key) };
+        io::copy(&mut source, &mut io::sink())?;
+        check_tag(source.hmac, tag)?;
+        let mut input = source.inner;
+        input.seek(SeekFrom::Start(start))?;
+
+        let source = MacReader { inner: input, hmac: HmacSha256::new(self.source_key) };
+        let mut reader = DecryptReader::new(self.from, source, self.from_mode);
+        if let (StreamMode::Cbc, Some(padding)) = (self.from_mode, self.from_padding) {
+            reader = reader.with_padding(padding)?;
+        }
+        let target = MacWriter { inner: output, hmac: HmacSha256::new(self.target_key) };
+        let mut writer = EncryptWriter::with_iv(self.to, target, self.to_mode, iv)?;
+        if let (StreamMode::Cbc, Some(padding)) = (self.to_mode, self.to_padding) {
+            writer = writer.with_padding(padding)?;
+        }
+
+        let mut sha = Sha256::default();
+        let copied = copy(&mut reader, &mut writer, &mut sha)
+            .and_then(|nbytes| check_tag(reader.into_inner().hmac, tag).map(|_| nbytes));
+        let nbytes = match copied {
+            Ok(nbytes) => nbytes,
+            Err(err) => {
+                // dropped writer would pad and write the last block
+                writer.abort();
+                return Err(err);
+            }
+        };
+        let target = writer.finish()?;
+        Ok(Report { nbytes, digest: sha.finalize(), tag: target.hmac.finalize() })
+    }
+}
+
+/// Copies plain text from `reader` to `writer`, returns its length.
+fn copy<R: Read, W: Write>(reader: &mut R, writer: &mut W, sha: &mut Sha256) -> io::Result<u64> {
+    let mut nbytes = 0u64;
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let result = loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break Ok(nbytes),
+            Ok(n) => n,
+            Err(err) => break Err(err),
+        };
+        sha.update(&chunk[..n]);
+        if let Err(err) = writer.write_all(&chunk[..n]) {
+            break Err(err);
+        }
+        nbytes += n as u64;
+    };
+    zeroize(&mut chunk);
+    result
+}
+
+/// Re-encrypts data produced by `from` (cipher and mode) into data of `to`
+/// (legacy CBC padding, random IV vector). The same `mac_key` checks `tag`
+/// of the source and signs the new data.
+pub fn reencrypt<D: BlockCipher, E: BlockCipher, R: Read + Seek, W: Write>(
+    from: (&D, StreamMode),
+    to: (&E, StreamMode),
+    mac_key: &[u8],
+    tag: &[u8],
+    input: R,
+    output: W,
+) -> io::Result<Report> {
+    Rotation::new(from.0, from.1, mac_key, to.0, to.1, mac_key).run(input, tag, output)
+}
+
+fn check_tag(hmac: HmacSha256, tag: &[u8]) -> io::Result<()> {
+    if !constant_time_eq(&hmac.finalize(), tag) {
+        return Err(CryptoError::AuthenticationFailed.into());
+    }
+    Ok(())
+}
+
+/// Reader computing HMAC of everything read through it.
+struct MacReader<R: Read> {
+    inner: R,
+    hmac: HmacSha256,
+}
+
+impl<R: Read> Read for MacReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hmac.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Writer computing HMAC of everything written through it.
+struct MacWriter<W: Write> {
+    inner: W,
+    hmac: HmacSha256,
+}
+
+impl<W: Write> Write for MacWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hmac.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::crypto::blowfish::Blowfish;
+    use crate::crypto::gost::Gost;
+    use crate::crypto::twofish::Twofish;
+
+    const MAC_KEY: &[u8] = b"old mac key";
+
+    fn sample(nbytes: usize) -> Vec<u8> {
+        (0..nbytes).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn test_blowfish_cbc_to_gost_ctr() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+
+        for nbytes in [1usize, 8, 100, 10_000] {
+            let plain = sample(nbytes);
+            let source = bf.encrypt_cbc(&plain).unwrap();
+            let tag = HmacSha256::mac(MAC_KEY, &source);
+
+            let mut output = Vec::new();
+            let report = reencrypt((&bf, StreamMode::Cbc), (&gt, StreamMode::Ctr), MAC_KEY, &tag, Cursor::new(&source), &mut output).unwrap();
+            assert_eq!(report.nbytes, nbytes as u64);
+            assert_eq!(report.digest, Sha256::digest(&plain));
+            assert_eq!(report.tag, HmacSha256::mac(MAC_KEY, &output));
+            assert_eq!(gt.decrypt_ctr(&output).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn test_block_sizes() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let tf = Twofish::new(b"Twofish test key").unwrap();
+        let plain = sample(5000);
+        let source = bf.encrypt_cbc_with(&plain, Padding::Pkcs7).unwrap();
+        let iv = [0x5au8; 16];
+
+        let mut output = Vec::new();
+        let rotation = Rotation::new(&bf, StreamMode::Cbc, MAC_KEY, &tf, StreamMode::Cbc, b"new mac key").with_padding(Padding::Pkcs7, Padding::Pkcs7);
+        let report = rotation.run_with_iv(Cursor::new(&source), &HmacSha256::mac(MAC_KEY, &source), &mut output, &iv).unwrap();
+        assert_eq!(output, tf.encrypt_cbc_iv_with(&plain, &iv, Padding::Pkcs7).unwrap());
+
+        // and back, the new tag authenticates the new data
+        let mut back = Vec::new();
+        let rotation = Rotation::new(&tf, StreamMode::Cbc, b"new mac key", &bf, StreamMode::Ctr, MAC_KEY).with_padding(Padding::Pkcs7, Padding::None);
+        rotation.run(Cursor::new(&output), &report.tag, &mut back).unwrap();
+        assert_eq!(bf.decrypt_ctr(&back).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_tampered() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let source = bf.encrypt_ctr(&sample(10_000)).unwrap();
+        let tag = HmacSha256::mac(MAC_KEY, &source);
+
+        // modified data, wrong key or tag: nothing is written
+        let mut modified = source.clone();
+        modified[5000] ^= 1;
+        for (data, key, tag) in [(&modified, MAC_KEY, &tag[..]), (&source, b"other key", &tag), (&source, MAC_KEY, &tag[..16])] {
+            let mut output = Vec::new();
+            let err = reencrypt((&bf, StreamMode::Ctr), (&gt, StreamMode::Cbc), key, tag, Cursor::new(data), &mut output).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(output.is_empty());
+        }
+        assert!(reencrypt((&bf, StreamMode::Ctr), (&gt, StreamMode::Cbc), b"", &tag, Cursor::new(&source), io::sink()).is_err());
+    }
+
+    /// Source which is modified when rewound for the second pass.
+    struct Changing(Cursor<Vec<u8>>);
+
+    impl Read for Changing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for Changing {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            if pos == SeekFrom::Start(0) {
+                self.0.get_mut()[5000] ^= 1;
+            }
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_changed_source() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let source = bf.encrypt_ctr(&sample(10_003)).unwrap();
+        let tag = HmacSha256::mac(MAC_KEY, &source);
+
+        // the second check fails, the last partial block is not padded and written
+        let mut output = Vec::new();
+        let rotation = Rotation::new(&bf, StreamMode::Ctr, MAC_KEY, &gt, StreamMode::Cbc, MAC_KEY);
+        let err = rotation.run(Changing(Cursor::new(source)), &tag, &mut output).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(output.len(), 8 + 10_000);
+    }
+
+    #[test]
+    fn test_bad_source() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let source = bf.encrypt_cbc_iv_with(&sample(100), &[7u8; 8], Padding::Pkcs7).unwrap();
+
+        // truncated cipher text with valid tag
+        let truncated = &source[..50];
+        let tag = HmacSha256::mac(MAC_KEY, truncated);
+        let mut output = Vec::new();
+        assert!(reencrypt((&bf, StreamMode::Cbc), (&gt, StreamMode::Ctr), MAC_KEY, &tag, Cursor::new(truncated), &mut output).is_err());
+        assert!(output.len().is_multiple_of(8));
+
+        // wrong cipher key is detected by padding check (fixed IV, so the result is stable)
+        let other = Blowfish::new(b"OTHERKEY").unwrap();
+        let tag = HmacSha256::mac(MAC_KEY, &source);
+        let rotation = Rotation::new(&other, StreamMode::Cbc, MAC_KEY, &gt, StreamMode::Ctr, MAC_KEY).with_padding(Padding::Pkcs7, Padding::None);
+        let err = rotation.run(Cursor::new(&source), &tag, io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,457 @@
+//! Streaming encryption/decryption with bounded memory.
+//! The produced bytes are the same as from `modes::encrypt_cbc_iv`
+//! and `modes::encrypt_ctr_nonce` (IV vector/nonce followed by cipher text).
+
+use std::io::{self, Read, Write};
+use crate::crypto::*;
+
+// number of bytes read from the source at once
+const CHUNK_SIZE: usize = 4096;
+
+/// Mode of operation used by stream adapters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamMode {
+    Cbc,
+    Ctr,
+}
+
+/********************************************************************
+*                                                                   *
+*                    E N C R Y P T   W R I T E R                    *
+*                                                                   *
+********************************************************************/
+
+/// Encrypts everything written to it and passes cipher text to the inner writer.
+/// `finish` should be called at the end, it writes the last (padded) block
+/// and reports errors. If the writer is dropped without `finish`, the last
+/// block is written anyway, but errors are ignored (as in `BufWriter`).
+/// A failed stream is dropped with `abort`, so it doesn't look complete.
+pub struct EncryptWriter<'a, C: BlockCipher, W: Write> {
+    cipher: &'a C,
+    // None after `finish`
+    inner: Option<W>,
+    mode: StreamMode,
+    // CBC: last cipher block, CTR: nonce
+    state: C::Block,
+    // number of encrypted blocks
+    counter: u64,
+    buffer: Vec<u8>,
+    // CBC padding, None means legacy padding (as `modes::encrypt_cbc`)
+    padding: Option<Padding>,
+}
+
+impl<'a, C: BlockCipher, W: Write> EncryptWriter<'a, C, W> {
+    /// Creates writer with random IV vector (nonce).
+    pub fn new(cipher: &'a C, inner: W, mode: StreamMode) -> io::Result<Self> {
+        Self::with_iv(cipher, inner, mode, &random_bytes(C::BLOCK_SIZE))
+    }
+
+    /// Creates writer with passed IV vector (nonce).
+    /// IV vector is written to the inner writer immediately.
+    pub fn with_iv(cipher: &'a C, mut inner: W, mode: StreamMode, iv: &[u8]) -> io::Result<Self> {
+        if iv.len() != C::BLOCK_SIZE {
+            return Err(CryptoError::InvalidIv { expected: C::BLOCK_SIZE, actual: iv.len() }.into());
+        }
+        inner.write_all(iv)?;
+        let state = load::<C>(iv);
+        Ok(EncryptWriter { cipher, inner: Some(inner), mode, state, counter: 0, buffer: Vec::with_capacity(C::BLOCK_SIZE), padding: None })
+    }
+
+    /// Selects padding of the last block (CBC mode only).
+    pub fn with_padding(mut self, padding: Padding) -> io::Result<Self> {
+        if self.mode != StreamMode::Cbc {
+            return Err(CryptoError::InvalidParameter("padding is used only in CBC mode").into());
+        }
+        self.padding = Some(padding);
+        Ok(self)
+    }
+
+    /// Encrypts remaining bytes (with padding in CBC mode),
+    /// flushes and returns the inner writer.
+    /// Nothing written in CBC mode with legacy padding is `EmptyInput` error
+    /// (as in `modes::encrypt_cbc`, such stream couldn't be decrypted).
+    pub fn finish(mut self) -> io::Result<W> {
+        let data = self.last_blocks()?;
+        let mut inner = self.inner.take().expect("writer is not finished");
+        inner.write_all(&data)?;
+        inner.flush()?;
+        Ok(inner)
+    }
+
+    /// Returns the inner writer without writing the last (incomplete) block,
+    /// the output ends after the last complete cipher block.
+    pub fn abort(mut self) -> W {
+        zeroize(&mut self.buffer);
+        self.buffer.clear();
+        self.inner.take().expect("writer is not finished")
+    }
+
+    /// Encrypts remaining bytes, returns the last cipher block(s).
+    fn last_blocks(&mut self) -> Result<Vec<u8>, CryptoError> {
+        let mut data = std::mem::take(&mut self.buffer);
+        match (self.mode, self.padding) {
+            (StreamMode::Cbc, Some(padding)) => {
+                data = padding.pad(&data, C::BLOCK_SIZE)?;
+                self.encrypt_blocks(&mut data);
+            }
+            (StreamMode::Cbc, None) => {
+                if data.is_empty() && self.counter == 0 {
+                    return Err(CryptoError::EmptyInput);
+                }
+                data = align_to_block(&data, C::BLOCK_SIZE);
+                self.encrypt_blocks(&mut data);
+            }
+            (StreamMode::Ctr, _) => {
+                if !data.is_empty() {
+                    let mut gamma = counter_block::<C>(self.state.as_ref(), self.counter);
+                    self.cipher.encrypt_block(&mut gamma);
+                    xor_into(&mut data, gamma.as_ref());
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    fn inner(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer is not finished")
+    }
+
+    /// Encrypts complete blocks in place.
+    fn encrypt_blocks(&mut self, data: &mut [u8]) {
+        for chunk in data.chunks_mut(C::BLOCK_SIZE) {
+            match self.mode {
+                StreamMode::Cbc => {
+                    xor_into(self.state.as_mut(), chunk);
+                    self.cipher.encrypt_block(&mut self.state);
+                    chunk.copy_from_slice(self.state.as_ref());
+                    self.counter = self.counter.wrapping_add(1);
+                }
+                StreamMode::Ctr => {
+                    let mut gamma = counter_block::<C>(self.state.as_ref(), self.counter);
+                    self.cipher.encrypt_block(&mut gamma);
+                    xor_into(chunk, gamma.as_ref());
+                    self.counter = self.counter.wrapping_add(1);
+                }
+            }
+        }
+    }
+}
+
+impl<C: BlockCipher, W: Write> Write for EncryptWriter<'_, C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        let n = self.buffer.len() - self.buffer.len() % C::BLOCK_SIZE;
+        if n > 0 {
+            let mut data: Vec<u8> = self.buffer.drain(..n).collect();
+            self.encrypt_blocks(&mut data);
+            self.inner().write_all(&data)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
+    }
+}
+
+impl<C: BlockCipher, W: Write> Drop for EncryptWriter<'_, C, W> {
+    fn drop(&mut self) {
+        if let Some(mut inner) = self.inner.take() {
+            if let Ok(data) = self.last_blocks() {
+                let _ = inner.write_all(&data).and_then(|_| inner.flush());
+            }
+        }
+    }
+}
+
+/********************************************************************
+*                                                                   *
+*                    D E C R Y P T   R E A D E R                    *
+*                                                                   *
+********************************************************************/
+
+/// Reads cipher text from the inner reader and returns plain text.
+pub struct DecryptReader<'a, C: BlockCipher, R: Read> {
+    cipher: &'a C,
+    inner: R,
+    mode: StreamMode,
+    // CBC: last cipher block, CTR: nonce
+    state: Option<C::Block>,
+    counter: u64,
+    // cipher text not decrypted yet
+    pending: Vec<u8>,
+    // decrypted plain text not returned yet
+    output: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    // number of bytes read from the inner reader (with IV vector)
+    nread: usize,
+    // CBC padding, None means legacy padding (as `modes::decrypt_cbc`)
+    padding: Option<Padding>,
+}
+
+impl<'a, C: BlockCipher, R: Read> DecryptReader<'a, C, R> {
+    /// Creates reader, IV vector (nonce) is read from the first block.
+    pub fn new(cipher: &'a C, inner: R, mode: StreamMode) -> Self {
+        DecryptReader {
+            cipher,
+            inner,
+            mode,
+            state: None,
+            counter: 0,
+            pending: Vec::with_capacity(CHUNK_SIZE + C::BLOCK_SIZE),
+            output: Vec::with_capacity(CHUNK_SIZE + C::BLOCK_SIZE),
+            pos: 0,
+            eof: false,
+            nread: 0,
+            padding: None,
+        }
+    }
+
+    /// Selects padding of the last block (CBC mode only).
+    /// Malformed padding is reported as `InvalidData` error.
+    pub fn with_padding(mut self, padding: Padding) -> io::Result<Self> {
+        if self.mode != StreamMode::Cbc {
+            return Err(CryptoError::InvalidParameter("padding is used only in CBC mode").into());
+        }
+        self.padding = Some(padding);
+        Ok(self)
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads next chunk of cipher text and decrypts what is possible.
+    fn fill(&mut self) -> io::Result<()> {
+        self.output.clear();
+        self.pos = 0;
+
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let n = self.inner.read(&mut chunk)?;
+        self.nread = self.nread.saturating_add(n);
+        if n == 0 {
+            self.eof = true;
+            // IV vector without cipher text is rejected (as in `modes::decrypt_cbc` and `modes::decrypt_ctr`)
+            let partial = self.mode == StreamMode::Cbc && !self.nread.is_multiple_of(C::BLOCK_SIZE);
+            if self.nread <= C::BLOCK_SIZE || partial {
+                return Err(CryptoError::TruncatedCiphertext { block_size: C::BLOCK_SIZE, actual: self.nread }.into());
+            }
+        }
+        self.pending.extend_from_slice(&chunk[..n]);
+
+        if self.state.is_none() {
+            if self.pending.len() < C::BLOCK_SIZE {
+                return Ok(());
+            }
+            self.state = Some(load::<C>(&self.pending));
+            self.pending.drain(..C::BLOCK_SIZE);
+        }
+
+        // in CBC mode the last block is kept until the end of data (padding)
+        let ready = match (self.mode, self.eof) {
+            (StreamMode::Cbc, false) => self.pending.len().saturating_sub(1) / C::BLOCK_SIZE * C::BLOCK_SIZE,
+            (StreamMode::Ctr, false) => self.pending.len() / C::BLOCK_SIZE * C::BLOCK_SIZE,
+            (_, true) => self.pending.len(),
+        };
+        let data: Vec<u8> = self.pending.drain(..ready).collect();
+        self.decrypt(&data);
+
+        if self.eof && self.mode == StreamMode::Cbc {
+            let last = self.output.len().saturating_sub(C::BLOCK_SIZE);
+            match self.padding {
+                Some(padding) => {
+                    let n = padding.unpad(&self.output[last..], C::BLOCK_SIZE)?.len();
+                    self.output.truncate(last + n);
+                }
+                None => {
+                    if let Some(idx) = padding_index(&self.output[last..]) {
+                        self.output.truncate(last + idx);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decrypts passed cipher text to the output buffer.
+    fn decrypt(&mut self, data: &[u8]) {
+        let state = self.state.as_mut().expect("IV vector is read before decryption");
+        for chunk in data.chunks(C::BLOCK_SIZE) {
+            match self.mode {
+                StreamMode::Cbc => {
+                    let c = load::<C>(chunk);
+                    let mut x = c;
+                    self.cipher.decrypt_block(&mut x);
+                    xor_into(x.as_mut(), state.as_ref());
+                    self.output.extend_from_slice(x.as_ref());
+                    *state = c;
+                }
+                StreamMode::Ctr => {
+                    let mut gamma = counter_block::<C>(state.as_ref(), self.counter);
+                    self.cipher.encrypt_block(&mut gamma);
+                    xor_into(gamma.as_mut(), chunk);
+                    self.output.extend_from_slice(&gamma.as_ref()[..chunk.len()]);
+                    self.counter = self.counter.wrapping_add(1);
+                }
+            }
+        }
+    }
+}
+
+impl<C: BlockCipher, R: Read> Read for DecryptReader<'_, C, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.output.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = buf.len().min(self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::blowfish::Blowfish;
+    use crate::crypto::gost::Gost;
+    use crate::crypto::modes;
+
+    fn sample(nbytes: usize) -> Vec<u8> {
+        (0..nbytes).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    fn encrypt<C: BlockCipher>(cipher: &C, mode: StreamMode, iv: &[u8], plain: &[u8]) -> Vec<u8> {
+        let mut writer = EncryptWriter::with_iv(cipher, Vec::new(), mode, iv).unwrap();
+        // uneven pieces
+        for chunk in plain.chunks(5) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn decrypt<C: BlockCipher>(cipher: &C, mode: StreamMode, data: &[u8]) -> Vec<u8> {
+        let mut plain = Vec::new();
+        DecryptReader::new(cipher, data, mode).read_to_end(&mut plain).unwrap();
+        plain
+    }
+
+    #[test]
+    fn test_same_as_modes() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let iv = [0xf8u8, 0xa4, 0x9e, 0x45, 0x40, 0xa5, 0x65, 0xc8];
+
+        for nbytes in [1usize, 7, 8, 9, 100, 10_000] {
+            let plain = sample(nbytes);
+
+            let cipher = encrypt(&gt, StreamMode::Cbc, &iv, &plain);
+            assert_eq!(cipher, modes::encrypt_cbc_iv(&gt, &plain, &iv).unwrap());
+            assert_eq!(decrypt(&gt, StreamMode::Cbc, &cipher), plain);
+
+            let cipher = encrypt(&gt, StreamMode::Ctr, &iv, &plain);
+            assert_eq!(cipher, modes::encrypt_ctr_nonce(&gt, &plain, &iv).unwrap());
+            assert_eq!(decrypt(&gt, StreamMode::Ctr, &cipher), plain);
+        }
+    }
+
+    #[test]
+    fn test_invalid_stream() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let mut plain = Vec::new();
+        let mut reader = DecryptReader::new(&bf, &[0u8; 13][..], StreamMode::Cbc);
+        assert!(reader.read_to_end(&mut plain).is_err());
+        assert!(EncryptWriter::with_iv(&bf, Vec::new(), StreamMode::Ctr, &[0u8; 4]).is_err());
+
+        // padding in CTR mode
+        assert!(EncryptWriter::new(&bf, Vec::new(), StreamMode::Ctr).unwrap().with_padding(Padding::Pkcs7).is_err());
+        assert!(DecryptReader::new(&bf, &[0u8; 16][..], StreamMode::Ctr).with_padding(Padding::Pkcs7).is_err());
+    }
+
+    #[test]
+    fn test_iv_only() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let iv = [0u8; 8];
+
+        // rejected the same way as by `modes` functions
+        assert!(modes::decrypt_cbc(&bf, &iv).is_err());
+        let mut plain = Vec::new();
+        let err = DecryptReader::new(&bf, &iv[..], StreamMode::Cbc).read_to_end(&mut plain).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut reader = DecryptReader::new(&bf, &iv[..], StreamMode::Cbc).with_padding(Padding::Pkcs7).unwrap();
+        assert!(reader.read_to_end(&mut plain).is_err());
+
+        assert!(modes::encrypt_cbc_iv(&bf, &[], &iv).is_err());
+        let err = EncryptWriter::with_iv(&bf, Vec::new(), StreamMode::Cbc, &iv).unwrap().finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        assert!(modes::decrypt_ctr(&bf, &iv).is_err());
+        let err = DecryptReader::new(&bf, &iv[..], StreamMode::Ctr).read_to_end(&mut plain).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bf = Blowfish::new(b"TESTKEY").unwrap();
+        let cipher = bf.encrypt_cbc(&sample(10_000)).unwrap();
+
+        // the whole length with IV vector is reported, as by `modes::decrypt_cbc`
+        let truncated = &cipher[..cipher.len() - 3];
+        let expected = modes::decrypt_cbc(&bf, truncated).unwrap_err();
+        assert_eq!(expected, CryptoError::TruncatedCiphertext { block_size: 8, actual: truncated.len() });
+        let mut plain = Vec::new();
+        let err = DecryptReader::new(&bf, truncated, StreamMode::Cbc).read_to_end(&mut plain).unwrap_err();
+        assert_eq!(err.get_ref().and_then(|e| e.downcast_ref::<CryptoError>()), Some(&expected));
+    }
+
+    #[test]
+    fn test_drop() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let iv = [0xf8u8, 0xa4, 0x9e, 0x45, 0x40, 0xa5, 0x65, 0xc8];
+        let plain = sample(100);
+
+        // without `finish` the last block is written by drop
+        let mut cipher = Vec::new();
+        let mut writer = EncryptWriter::with_iv(&gt, &mut cipher, StreamMode::Cbc, &iv).unwrap();
+        writer.write_all(&plain).unwrap();
+        drop(writer);
+        assert_eq!(cipher, modes::encrypt_cbc_iv(&gt, &plain, &iv).unwrap());
+
+        // `abort` writes nothing after the last complete block
+        let mut cipher = Vec::new();
+        let mut writer = EncryptWriter::with_iv(&gt, &mut cipher, StreamMode::Ctr, &iv).unwrap();
+        writer.write_all(&plain).unwrap();
+        writer.abort();
+        assert_eq!(cipher, modes::encrypt_ctr_nonce(&gt, &plain, &iv).unwrap()[..8 + 96]);
+    }
+
+    #[test]
+    fn test_padding() {
+        let gt = Gost::new(b"12345678901234567890123456789012").unwrap();
+        let iv = [0xf8u8, 0xa4, 0x9e, 0x45, 0x40, 0xa5, 0x65, 0xc8];
+
+        for nbytes in [0usize, 8, 13, 5000] {
+            let plain = sample(nbytes);
+            let mut writer = EncryptWriter::with_iv(&gt, Vec::new(), StreamMode::Cbc, &iv).unwrap().with_padding(Padding::Pkcs7).unwrap();
+            writer.write_all(&plain).unwrap();
+            let cipher = writer.finish().unwrap();
+            if nbytes > 0 {
+                assert_eq!(cipher, modes::encrypt_cbc_iv_with(&gt, &plain, &iv, Padding::Pkcs7).unwrap());
+            }
+
+            let mut decrypted = Vec::new();
+            let mut reader = DecryptReader::new(&gt, &cipher[..], StreamMode::Cbc).with_padding(Padding::Pkcs7).unwrap();
+            reader.read_to_end(&mut decrypted).unwrap();
+            assert_eq!(decrypted, plain);
+
+            let mut reader = DecryptReader::new(&gt, &cipher[..], StreamMode::Cbc).with_padding(Padding::None).unwrap();
+            decrypted.clear();
+            reader.read_to_end(&mut decrypted).unwrap();
+            assert_eq!(decrypted.len(), cipher.len() - 8);
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! Twofish (Schneier et al., AES finalist): 128 bit block, 128/192/256 bit key.
+//!
+//! Blocks and keys are read as little-endian words like in the reference
+//! implementation. Key-dependent S-boxes are combined with the MDS matrix
+//! into four tables during key setup.
+
+use lazy_static::lazy_static;
+
+use crate::crypto::*;
+use crate::crypto::modes::Cts;
+use crate::crypto::kdf::{self, KdfParams};
+
+pub struct Twofish {
+    k: [u32; 40],
+    s: [[u32; 256]; 4],
+}
+
+const BLOCK_SIZE: usize = 16;
+const MIN_KEY_SIZE: usize = 16;
+const MAX_KEY_SIZE: usize = 32;
+const ROUNDS: usize = 16;
+const RHO: u32 = 0x01010101;
+// primitive polynomials of MDS and RS codes
+const MDS_POLY: u8 = 0x69;
+const RS_POLY: u8 = 0x4d;
+
+lazy_static! {
+    static ref Q: [[u8; 256]; 2] = [permutation(&Q0_T), permutation(&Q1_T)];
+}
+
+impl Twofish {
+    /// Creates twofish-object for passed string|&str as key.
+    pub fn new_with_string<T: AsRef<str>>(key: T) -> Result<Twofish, CryptoError> {
+        Twofish::new(key.as_ref().as_bytes())
+    }
+
+    /// Creates twofish-object with key derived from password (maximal key length).
+    pub fn with_password(password: &[u8], salt: &[u8], params: &KdfParams) -> Result<Twofish, CryptoError> {
+        let mut key = [0u8; MAX_KEY_SIZE];
+        let tf = kdf::derive_key(password, salt, params, &mut key).and_then(|_| Twofish::new(&key));
+        zeroize(&mut key);
+        tf
+    }
+
+    /// Creates twofish-object for passed slice as key (16 - 32 bytes).
+    /// Keys of other lengths than 16, 24 and 32 bytes are padded with zeros
+    /// to the next of them. Key schedule words are wiped, but the object
+    /// is returned by value, so a copy of it may stay on the stack unwiped.
+    pub fn new(key: &[u8]) -> Result<Twofish, CryptoError> {
+        let keylen = key.len();
+        if !(MIN_KEY_SIZE..=MAX_KEY_SIZE).contains(&keylen) {
+            return Err(CryptoError::InvalidKeyLength { min: MIN_KEY_SIZE, max: MAX_KEY_SIZE, actual: keylen });
+        }
+
+        let mut m = [0u8; MAX_KEY_SIZE];
+        m[..keylen].copy_from_slice(key);
+        let n = keylen.div_ceil(8);
+        let n = if n == 3 { 3 } else { n.max(2).next_power_of_two() };
+        let m = &m[..8 * n];
+
+        // even and odd words of the key and S-box key (in reversed order)
+        let mut me = [0u32; 4];
+        let mut mo = [0u32; 4];
+        let mut sk = [0u32; 4];
+        for i in 0..n {
+            me[i] = u32::from_le_bytes(m[8 * i..8 * i + 4].try_into().unwrap());
+            mo[i] = u32::from_le_bytes(m[8 * i + 4..8 * i + 8].try_into().unwrap());
+            sk[n - 1 - i] = rs_mul(&m[8 * i..8 * i + 8]);
+        }
+
+        let mut tf = Twofish { k: [0; 40], s: [[0; 256]; 4] };
+        for i in 0..20 {
+            let a = h(2 * i as u32 * RHO, &me[..n]);
+            let b = h((2 * i as u32 + 1) * RHO, &mo[..n]).rotate_left(8);
+            tf.k[2 * i] = a.wrapping_add(b);
+            tf.k[2 * i + 1] = a.wrapping_add(b.wrapping_mul(2)).rotate_left(9);
+        }
+        for (j, table) in tf.s.iter_mut().enumerate() {
+            for (b, entry) in table.iter_mut().enumerate() {
+                *entry = mds_column(j, sbox(j, b as u8, &sk[..n]));
+            }
+        }
+
+        zeroize(&mut me);
+        zeroize(&mut mo);
+        zeroize(&mut sk);
+        Ok(tf)
+    }
+
+    /// Function g: key-dependent S-boxes and MDS matrix.
+    /// With `constant-time` feature tables are read without secret indexes.
+    #[inline]
+    fn g(&self, x: u32) -> u32 {
+        if cfg!(feature = "constant-time") {
+            self.g_constant_time(x)
+        } else {
+            self.g_table(x)
+        }
+    }
+
+    /// Function g with direct table lookups (fast, leaks through cache timing).
+    fn g_table(&self, x: u32) -> u32 {
+        let [a, b, c, d] = x.to_le_bytes().map(usize::from);
+        self.s[0][a] ^ self.s[1][b] ^ self.s[2][c] ^ self.s[3][d]
+    }
+
+    /// Function g scanning whole tables with masks (cache-timing resistant).
+    fn g_constant_time(&self, x: u32) -> u32 {
+        x.to_le_bytes()
+            .iter()
+            .zip(&self.s)
+            .fold(0, |acc, (&b, table)| acc ^ ct_select(table.iter().copied(), b as usize))
+    }
+
+    /// Encrypts four u32 words.
+    pub fn encrypt(&self, x: [u32; 4]) -> [u32; 4] {
+        let k = &self.k;
+        let [mut x0, mut x1, mut x2, mut x3] = [x[0] ^ k[0], x[1] ^ k[1], x[2] ^ k[2], x[3] ^ k[3]];
+        for r in (0..ROUNDS).step_by(2) {
+            let (t0, t1) = (self.g(x0), self.g(x1.rotate_left(8)));
+            x2 = (x2 ^ t0.wrapping_add(t1).wrapping_add(k[2 * r + 8])).rotate_right(1);
+            x3 = x3.rotate_left(1) ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[2 * r + 9]);
+
+            let (t0, t1) = (self.g(x2), self.g(x3.rotate_left(8)));
+            x0 = (x0 ^ t0.wrapping_add(t1).wrapping_add(k[2 * r + 10])).rotate_right(1);
+            x1 = x1.rotate_left(1) ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[2 * r + 11]);
+        }
+        [x2 ^ k[4], x3 ^ k[5], x0 ^ k[6], x1 ^ k[7]]
+    }
+
+    /// Decrypts four u32 words.
+    pub fn decrypt(&self, x: [u32; 4]) -> [u32; 4] {
+        let k = &self.k;
+        let [mut x2, mut x3, mut x0, mut x1] = [x[0] ^ k[4], x[1] ^ k[5], x[2] ^ k[6], x[3] ^ k[7]];
+        for r in (0..ROUNDS).step_by(2).rev() {
+            let (t0, t1) = (self.g(x2), self.g(x3.rotate_left(8)));
+            x0 = x0.rotate_left(1) ^ t0.wrapping_add(t1).wrapping_add(k[2 * r + 10]);
+            x1 = (x1 ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[2 * r + 11])).rotate_right(1);
+
+            let (t0, t1) = (self.g(x0), self.g(x1.rotate_left(8)));
+            x2 = x2.rotate_left(1) ^ t0.wrapping_add(t1).wrapping_add(k[2 * r + 8]);
+            x3 = (x3 ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[2 * r + 9])).rotate_right(1);
+        }
+        [x0 ^ k[0], x1 ^ k[1], x2 ^ k[2], x3 ^ k[3]]
+    }
+
+    /// Encrypts passed plain text (ECB mode).
+    pub fn encrypt_ecb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb(self, input)
+    }
+
+    /// Decrypts passed cipher text (ECB mode).
+    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb(self, cipher)
+    }
+
+    /// Encrypts passed plain-text.
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc(self, input)
+    }
+
+    /// Encrypts plain-text with passed IV vector.
+    pub fn encrypt_cbc_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_iv(self, input, iv)
+    }
+
+    /// Decrypts passed cipher-text (CBC mode).
+    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc(self, cipher)
+    }
+
+    /// Encrypts passed plain text with selected padding (ECB mode).
+    pub fn encrypt_ecb_with(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ecb_with(self, input, padding)
+    }
+
+    /// Decrypts passed cipher text and checks selected padding (ECB mode).
+    pub fn decrypt_ecb_with(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ecb_with(self, cipher, padding)
+    }
+
+    /// Encrypts passed plain-text with selected padding (CBC mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc_with(&self, input: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_with(self, input, padding)
+    }
+
+    /// Encrypts plain-text with passed IV vector and selected padding (CBC mode).
+    pub fn encrypt_cbc_iv_with(&self, input: &[u8], iv: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_iv_with(self, input, iv, padding)
+    }
+
+    /// Decrypts passed cipher-text and checks selected padding (CBC mode).
+    pub fn decrypt_cbc_with(&self, cipher: &[u8], padding: Padding) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc_with(self, cipher, padding)
+    }
+
+    /// Encrypts passed plain-text with ciphertext stealing (CBC mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cbc_cts(&self, input: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_cts(self, input, variant)
+    }
+
+    /// Encrypts plain-text with passed IV vector and ciphertext stealing (CBC mode).
+    pub fn encrypt_cbc_cts_iv(&self, input: &[u8], iv: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cbc_cts_iv(self, input, iv, variant)
+    }
+
+    /// Decrypts passed cipher-text with ciphertext stealing (CBC mode).
+    pub fn decrypt_cbc_cts(&self, cipher: &[u8], variant: Cts) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cbc_cts(self, cipher, variant)
+    }
+
+    /// Encrypts passed plain-text (CTR mode).
+    /// Before encryption creates random nonce.
+    pub fn encrypt_ctr(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ctr(self, input)
+    }
+
+    /// Encrypts plain-text with passed nonce (CTR mode).
+    pub fn encrypt_ctr_nonce(&self, input: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ctr_nonce(self, input, nonce)
+    }
+
+    /// Decrypts passed cipher-text (CTR mode).
+    pub fn decrypt_ctr(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ctr(self, cipher)
+    }
+
+    /// Encrypts/decrypts data in place starting at byte `offset` of the key stream (CTR mode).
+    pub fn apply_ctr(&self, nonce: &[u8], offset: u64, data: &mut [u8]) -> Result<(), CryptoError> {
+        modes::apply_ctr(self, nonce, offset, data)
+    }
+
+    /// Encrypts passed plain-text (CFB mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_cfb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cfb(self, input)
+    }
+
+    /// Encrypts plain-text with passed IV vector (CFB mode).
+    pub fn encrypt_cfb_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_cfb_iv(self, input, iv)
+    }
+
+    /// Decrypts passed cipher-text (CFB mode).
+    pub fn decrypt_cfb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_cfb(self, cipher)
+    }
+
+    /// Encrypts passed plain-text (OFB mode).
+    /// Before encryption creates IV vector.
+    pub fn encrypt_ofb(&self, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ofb(self, input)
+    }
+
+    /// Encrypts plain-text with passed IV vector (OFB mode).
+    pub fn encrypt_ofb_iv(&self, input: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::encrypt_ofb_iv(self, input, iv)
+    }
+
+    /// Decrypts passed cipher-text (OFB mode).
+    pub fn decrypt_ofb(&self, cipher: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        modes::decrypt_ofb(self, cipher)
+    }
+
+    /// Overwrites round keys and key-dependent tables with zeros (called on drop).
+    fn wipe(&mut self) {
+        zeroize(&mut self.k);
+        for s in self.s.iter_mut() {
+            zeroize(s);
+        }
+    }
+}
+
+impl Drop for Twofish {
+    fn drop(&mut self) {
+        self.wipe();
+    }
+}
+
+impl BlockCipher for Twofish {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    type Block = [u8; BLOCK_SIZE];
+
+    fn new(key: &[u8]) -> Result<Twofish, CryptoError> {
+        Twofish::new(key)
+    }
+
+    fn encrypt_block(&self, block: &mut Self::Block) {
+        *block = words2block(self.encrypt(block2words(block)));
+    }
+
+    fn decrypt_block(&self, block: &mut Self::Block) {
+        *block = words2block(self.decrypt(block2words(block)));
+    }
+}
+
+/****************************************************************
+*                                                               *
+*                  K E Y   S C H E D U L E                      *
+*                                                               *
+****************************************************************/
+
+/// Function h: S-boxes of byte positions keyed with words of `l`, then MDS.
+fn h(x: u32, l: &[u32]) -> u32 {
+    x.to_le_bytes().iter().enumerate().fold(0, |acc, (j, &b)| acc ^ mds_column(j, sbox(j, b, l)))
+}
+
+/// Key-dependent S-box of byte position j: chain of q permutations
+/// with bytes of `l` (2 - 4 words) XORed in between.
+fn sbox(j: usize, mut y: u8, l: &[u32]) -> u8 {
+    // permutations used before XOR with l[3], l[2], l[1], l[0] and at the end
+    const ORDER: [[usize; 5]; 4] = [
+        [1, 1, 0, 0, 1],
+        [0, 1, 1, 0, 0],
+        [0, 0, 0, 1, 1],
+        [1, 0, 1, 1, 0],
+    ];
+    let q = &ORDER[j];
+    for i in (0..l.len()).rev() {
+        y = Q[q[3 - i]][y as usize] ^ l[i].to_le_bytes()[j];
+    }
+    Q[q[4]][y as usize]
+}
+
+/// Column j of MDS matrix multiplied by byte.
+fn mds_column(j: usize, b: u8) -> u32 {
+    const MDS: [[u8; 4]; 4] = [
+        [0x01, 0xef, 0x5b, 0x5b],
+        [0x5b, 0xef, 0xef, 0x01],
+        [0xef, 0x5b, 0x01, 0xef],
+        [0xef, 0x01, 0xef, 0x5b],
+    ];
+    u32::from_le_bytes(MDS.map(|row| gf_mul(row[j], b, MDS_POLY)))
+}
+
+/// Multiplies 8 bytes of key by RS matrix.
+fn rs_mul(m: &[u8]) -> u32 {
+    const RS: [[u8; 8]; 4] = [
+        [0x01, 0xa4, 0x55, 0x87, 0x5a, 0x58, 0xdb, 0x9e],
+        [0xa4, 0x56, 0x82, 0xf3, 0x1e, 0xc6, 0x68, 0xe5],
+        [0x02, 0xa1, 0xfc, 0xc1, 0x47, 0xae, 0x3d, 0x19],
+        [0xa4, 0x55, 0x87, 0x5a, 0x58, 0xdb, 0x9e, 0x03],
+    ];
+    u32::from_le_bytes(RS.map(|row| row.iter().zip(m).fold(0, |acc, (&r, &x)| acc ^ gf_mul(r, x, RS_POLY))))
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + poly.
+fn gf_mul(mut a: u8, mut b: u8, poly: u8) -> u8 {
+    let mut r = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            r ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= poly;
+        }
+        b >>= 1;
+    }
+    r
+}
+
+/// Builds permutation q0 or q1 from its 4 bit tables.
+fn permutation(t: &[[u8; 16]; 4]) -> [u8; 256] {
+    let ror4 = |x: u8| ((x >> 1) | (x << 3)) & 0xf;
+    std::array::from_fn(|x| {
+        let (a, b) = ((x >> 4) as u8, (x & 0xf) as u8);
+        let (a, b) = (t[0][(a ^ b) as usize], t[1][(a ^ ror4(b) ^ ((a << 3) & 0xf)) as usize]);
+        let (a, b) = (t[2][(a ^ b) as usize], t[3][(a ^ ror4(b) ^ ((a << 3) & 0xf)) as usize]);
+        (b << 4) | a
+    })
+}
+
+/// Converts block of bytes into four little-endian words.
+fn block2words(block: &[u8; BLOCK_SIZE]) -> [u32; 4] {
+    std::array::from_fn(|i| u32::from_le_bytes(block[4 * i..4 * i + 4].try_into().unwrap()))
+}
+
+/// Converts four words into block of bytes (little-endian).
+fn words2block(words: [u32; 4]) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    for (chunk, w) in block.chunks_mut(4).zip(words) {
+        chunk.copy_from_slice(&w.to_le_bytes());
+    }
+    block
+}
+
+/********************************************************************
+*                                                                   *
+*                            D A T A                                *
+*                                                                   *
+********************************************************************/
+
+// 4 bit tables of permutations q0 and q1
+const Q0_T: [[u8; 16]; 4] = [
+    [0x8, 0x1, 0x7, 0xd, 0x6, 0xf, 0x3, 0x2, 0x0, 0xb, 0x5, 0x9, 0xe, 0xc, 0xa, 0x4],
+    [0xe, 0xc, 0xb, 0x8, 0x1, 0x2, 0x3, 0x5, 0xf, 0x4, 0xa, 0x6, 0x7, 0x0, 0x9, 0xd],
+    [0xb, 0xa, 0x5, 0xe, 0x6, 0xd, 0x9, 0x0, 0xc, 0x8, 0xf, 0x3, 0x2, 0x4, 0x7, 0x1],
+    [0xd, 0x7, 0xf, 0x4, 0x1, 0x2, 0x6, 0xe, 0x9, 0xb, 0x3, 0x0, 0x8, 0x5, 0xc, 0xa],
+];
+
+const Q1_T: [[u8; 16]; 4] = [
+    [0x2, 0x8, 0xb, 0xd, 0xf, 0x7, 0x6, 0xe, 0x3, 0x1, 0x9, 0x4, 0x0, 0xa, 0xc, 0x5],
+    [0x1, 0xe, 0x2, 0xb, 0x4, 0xc, 0x3, 0x7, 0x6, 0xd, 0xa, 0x5, 0xf, 0x9, 0x0, 0x8],
+    [0x4, 0xc, 0x7, 0x5, 0x1, 0x6, 0x9, 0xa, 0x0, 0xe, 0xd, 0x8, 0x2, 0xb, 0x3, 0xf],
+    [0xb, 0x9, 0x5, 0x1, 0xc, 0x3, 0xd, 0xe, 0x6, 0x4, 0x7, 0xf, 0x2, 0x0, 0x8, 0xa],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::vectors::{hex, parse};
+    use crate::crypto::tests::sample_words;
+
+    #[test]
+    fn test_permutations() {
+        assert_eq!(Q[0][..4], [0xa9, 0x67, 0xb3, 0xe8]);
+        assert_eq!(Q[1][..4], [0x75, 0xf3, 0xc6, 0xf4]);
+    }
+
+    #[test]
+    fn test_ecb_vectors() {
+        for v in parse(include_str!("testdata/twofish_ecb.txt")) {
+            let tf = Twofish::new(&hex(v[0])).unwrap();
+            let (plain, cipher) = (hex(v[1]), hex(v[2]));
+            assert_eq!(tf.encrypt_ecb_with(&plain, Padding::None).unwrap(), cipher, "key {}", v[0]);
+            assert_eq!(tf.decrypt_ecb_with(&cipher, Padding::None).unwrap(), plain, "key {}", v[0]);
+        }
+    }
+
+    /// Iterated tests of ecb_tbl.txt: the next key is the previous
+    /// plain text followed by the previous key, plain text is the previous cipher text.
+    #[test]
+    fn test_iterated() {
+        let expected = [
+            (16, "5d9d4eeffa9151575524f115815a12e0"),
+            (24, "e75449212beef9f4a390bd860a640941"),
+            (32, "37fe26ff1cf66175f5ddf4c33b97a205"),
+        ];
+        for (keylen, cipher) in expected {
+            let mut key = vec![0u8; keylen];
+            let mut plain = [0u8; BLOCK_SIZE];
+            let mut block = [0u8; BLOCK_SIZE];
+            for _ in 0..49 {
+                let tf = Twofish::new(&key).unwrap();
+                block = plain;
+                tf.encrypt_block(&mut block);
+                key = [&plain[..], &key].concat()[..keylen].to_vec();
+                plain = block;
+            }
+            assert_eq!(block[..], hex(cipher)[..], "key length {}", keylen);
+        }
+    }
+
+    #[test]
+    fn test_modes() {
+        let tf = Twofish::new_with_string("Twofish test key").unwrap();
+        let plain = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy".as_bytes();
+
+        let cipher = tf.encrypt_cbc_with(plain, Padding::Pkcs7).unwrap();
+        assert_eq!(tf.decrypt_cbc_with(&cipher, Padding::Pkcs7).unwrap(), plain);
+        let cipher = tf.encrypt_cbc_cts(plain, Cts::Cs3).unwrap();
+        assert_eq!(tf.decrypt_cbc_cts(&cipher, Cts::Cs3).unwrap(), plain);
+        let cipher = tf.encrypt_ctr(plain).unwrap();
+        assert_eq!(tf.decrypt_ctr(&cipher).unwrap(), plain);
+        let cipher = tf.encrypt_cfb(plain).unwrap();
+        assert_eq!(tf.decrypt_cfb(&cipher).unwrap(), plain);
+        let cipher = tf.encrypt_ofb(plain).unwrap();
+        assert_eq!(tf.decrypt_ofb(&cipher).unwrap(), plain);
+
+        assert_eq!(
+            Twofish::new(&[0u8; 15]).err(),
+            Some(CryptoError::InvalidKeyLength { min: MIN_KEY_SIZE, max: MAX_KEY_SIZE, actual: 15 })
+        );
+        assert!(Twofish::new(&[0u8; 33]).is_err());
+    }
+
+    #[test]
+    fn test_short_key() {
+        // keys are padded with zeros to 24 or 32 bytes
+        let key = hex("0123456789abcdeffedcba98765432100011223344");
+        let padded = [&key[..], &[0u8; 3]].concat();
+        let plain = [0x5au8; BLOCK_SIZE];
+        let encrypted = Twofish::new(&key).unwrap().encrypt_ecb_with(&plain, Padding::None).unwrap();
+        assert_eq!(Twofish::new(&padded).unwrap().encrypt_ecb_with(&plain, Padding::None).unwrap(), encrypted);
+    }
+
+    /// Masked scan of the key-dependent S-boxes (with MDS folded in)
+    /// agrees with direct lookups of g.
+    #[test]
+    fn test_constant_time() {
+        let tf = Twofish::new(b"TESTKEY TESTKEY!").unwrap();
+        for x in sample_words() {
+            assert_eq!(tf.g_constant_time(x), tf.g_table(x), "x = {:#010x}", x);
+        }
+    }
+
+    #[test]
+    fn test_wipe() {
+        let mut tf = Twofish::new(b"TESTKEY TESTKEY!").unwrap();
+        tf.wipe();
+        assert_eq!(tf.k, [0u32; 40]);
+        assert!(tf.s.iter().flatten().all(|&x| x == 0));
+    }
+}
@@ -0,0 +1,17 @@
+//! Helpers for data-driven tests with known-answer vectors
+//! (files in `crypto/testdata`).
+
+/// Splits vector file into lines of whitespace separated fields.
+/// Empty lines and lines starting with '#' are skipped.
+pub fn parse(text: &str) -> Vec<Vec<&str>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split_whitespace().collect())
+        .collect()
+}
+
+/// Converts hex string into bytes.
+pub fn hex(text: &str) -> Vec<u8> {
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+}
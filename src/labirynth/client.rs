@@ -30,9 +30,9 @@ impl<'s> Theseus<'s> {
 
         let possible_steps = self.server.possible_steps(current);
         for p in &possible_steps {
-            if !route.contains(&p) {
+            if !route.contains(p) {
                 let mut new_route = route.clone();
-                new_route.append(p.clone());
+                new_route.append(*p);
                 self.search_route(new_route);
             }
         }
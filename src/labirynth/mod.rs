@@ -50,7 +50,7 @@ impl Point {
 
 impl Clone for Point {
     fn clone(&self) -> Self {
-        Point{x: self.x, y: self.y, exit: self.exit}
+        *self
     }
 }
 
@@ -69,9 +69,7 @@ impl Route {
     /// Creates new route for passed point.
     /// No route can be empty.
     pub fn new(p: Point) -> Route {
-        let mut data: Vec<Point> = Vec::new();
-        data.push(p);
-        Route(data)
+        Route(vec![p])
     }
 
     /// Chekcs if passed point is already in the route.
@@ -89,7 +87,7 @@ impl Route {
     pub fn last_point(&self) -> Point {
         let i = self.0.len() - 1;
         let p = &self.0[i];
-        p.clone()
+        *p
     }
 
     /// Appends next point to route.
@@ -42,8 +42,8 @@ pub fn to_int(text: &str) -> Option<isize> {
         None => return None
     };
     // the rest
-    for i in 1..text.len() {
-        let current = match ROMAN.get(&buffer[i]) {
+    for c in &buffer[1..] {
+        let current = match ROMAN.get(c) {
             Some(x) => *x,
             None => return None
         };